@@ -0,0 +1,298 @@
+use chess_rust::engine::SearchLimits;
+use chess_rust::game::Board;
+use chess_rust::match_runner::{Adjudication, EngineConfig, MatchConfig, Termination, run_match};
+use chess_rust::pgn::{self, PgnReader, Tags};
+use chess_rust::stats::Sprt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use std::{env, process};
+
+const USAGE: &str = "usage: match --engine CMD [--name NAME] [--option NAME=VALUE]... \
+--engine CMD [...]
+       [--games N] [--depth N | --nodes N | --movetime MS] [--openings FILE.epd|FILE.pgn]
+       [--pgn FILE] [--resign CP[,MOVES]] [--draw CP[,MOVES[,AFTER]]] [--tb PIECES]
+       [--max-moves N] [--sprt ELO0,ELO1[,ALPHA,BETA]]";
+
+struct Options {
+    engines: Vec<EngineConfig>,
+    limits: SearchLimits,
+    config: MatchConfig,
+    pgn_path: String,
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1).collect());
+    let [first, second] = &options.engines[..] else {
+        usage("exactly two engines are needed");
+    };
+
+    let start = |config: &EngineConfig| match config.start(&options.limits) {
+        Ok(player) => player,
+        Err(e) => {
+            eprintln!("could not start {}: {}", config.name, e);
+            process::exit(1);
+        }
+    };
+    let mut first_player = start(first);
+    let mut second_player = start(second);
+
+    let mut pgn_file = match File::create(&options.pgn_path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("could not create {}: {}", options.pgn_path, e);
+            process::exit(1);
+        }
+    };
+
+    let date = pgn::today();
+    let score = run_match(
+        &mut first_player,
+        &mut second_player,
+        &options.config,
+        |game, score| {
+            let (white, black) = if game.first_is_white {
+                (&first.name, &second.name)
+            } else {
+                (&second.name, &first.name)
+            };
+            let record = &game.record;
+            let tags = Tags {
+                event: "Engine match".to_string(),
+                date: date.clone(),
+                round: game.round.to_string(),
+                white: white.clone(),
+                black: black.clone(),
+                extra: vec![(
+                    "Termination".to_string(),
+                    record.termination.pgn_tag().to_string(),
+                )],
+                ..Tags::default()
+            };
+            let text = pgn::write_pgn(&record.board, &tags, record.result);
+            if let Err(e) = write!(pgn_file, "{}", text) {
+                eprintln!("could not write {}: {}", options.pgn_path, e);
+            }
+
+            let detail = match &record.termination {
+                Termination::Forfeit(reason) => format!(" ({})", reason),
+                termination => format!(" ({})", termination.pgn_tag()),
+            };
+            println!(
+                "Game {}/{}: {} - {} {}{}   {}: {}",
+                game.round,
+                options.config.games,
+                white,
+                black,
+                record.result.as_str(),
+                detail,
+                first.name,
+                score
+            );
+        },
+    );
+
+    println!();
+    println!(
+        "Score of {} vs {}: {} [{:.3}] {} games",
+        first.name,
+        second.name,
+        score,
+        score.score().unwrap_or(0.5),
+        score.games()
+    );
+    match score.elo() {
+        Some(elo) => println!("Elo difference: {}", elo),
+        None => println!("Elo difference: undefined"),
+    }
+    if let Some(sprt) = options.config.sprt {
+        let (lower, upper) = sprt.bounds();
+        println!(
+            "SPRT ({} vs {} Elo): LLR {:.2} ({:.2}, {:.2}), {}",
+            sprt.elo0,
+            sprt.elo1,
+            sprt.llr(&score),
+            lower,
+            upper,
+            sprt.verdict(&score)
+        );
+    }
+    println!("Games written to {}", options.pgn_path);
+}
+
+fn parse_args(args: Vec<String>) -> Options {
+    let mut options = Options {
+        engines: Vec::new(),
+        limits: SearchLimits::default(),
+        config: MatchConfig {
+            games: 10,
+            openings: Vec::new(),
+            adjudication: Adjudication::default(),
+            sprt: None,
+        },
+        pgn_path: "match.pgn".to_string(),
+    };
+
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| usage(&format!("{} needs a value", flag)))
+        };
+        match flag.as_str() {
+            "--engine" => {
+                let command: Vec<String> = value().split_whitespace().map(str::to_string).collect();
+                let Some(path) = command.first() else {
+                    usage("--engine needs a command");
+                };
+                let name = Path::new(path)
+                    .file_stem()
+                    .map_or(path.clone(), |stem| stem.to_string_lossy().into_owned());
+                options.engines.push(EngineConfig {
+                    name,
+                    command,
+                    options: Vec::new(),
+                });
+            }
+            "--name" => current_engine(&mut options.engines, &flag).name = value(),
+            "--option" => {
+                let text = value();
+                let Some((name, option)) = text.split_once('=') else {
+                    usage("--option takes NAME=VALUE");
+                };
+                current_engine(&mut options.engines, &flag)
+                    .options
+                    .push((name.to_string(), option.to_string()));
+            }
+            "--games" => options.config.games = number(&value(), &flag),
+            "--depth" => options.limits.depth = Some(number(&value(), &flag)),
+            "--nodes" => options.limits.nodes = Some(number(&value(), &flag)),
+            "--movetime" => {
+                options.limits.movetime = Some(Duration::from_millis(number(&value(), &flag)))
+            }
+            "--openings" => options.config.openings = read_openings(&value()),
+            "--pgn" => options.pgn_path = value(),
+            "--resign" => {
+                let values = numbers(&value(), &flag, 1..=2);
+                let adjudication = &mut options.config.adjudication;
+                adjudication.resign_score = Some(values[0] as i32);
+                if let Some(&moves) = values.get(1) {
+                    adjudication.resign_moves = moves as usize;
+                }
+            }
+            "--draw" => {
+                let values = numbers(&value(), &flag, 1..=3);
+                let adjudication = &mut options.config.adjudication;
+                adjudication.draw_score = Some(values[0] as i32);
+                if let Some(&moves) = values.get(1) {
+                    adjudication.draw_moves = moves as usize;
+                }
+                if let Some(&after) = values.get(2) {
+                    adjudication.draw_after = after as u32;
+                }
+            }
+            "--tb" => options.config.adjudication.tablebase_pieces = Some(number(&value(), &flag)),
+            "--max-moves" => options.config.adjudication.max_moves = Some(number(&value(), &flag)),
+            "--sprt" => {
+                let text = value();
+                let values: Vec<f64> = text.split(',').filter_map(|v| v.parse().ok()).collect();
+                let mut sprt = Sprt::default();
+                match values[..] {
+                    [elo0, elo1] => (sprt.elo0, sprt.elo1) = (elo0, elo1),
+                    [elo0, elo1, alpha, beta] => {
+                        sprt = Sprt {
+                            elo0,
+                            elo1,
+                            alpha,
+                            beta,
+                        }
+                    }
+                    _ => usage(&format!("invalid value '{}' for --sprt", text)),
+                }
+                options.config.sprt = Some(sprt);
+            }
+            _ => usage(&format!("unknown argument '{}'", flag)),
+        }
+    }
+
+    if options.limits == SearchLimits::default() {
+        options.limits.movetime = Some(Duration::from_millis(100));
+    }
+    if let [first, second] = &mut options.engines[..]
+        && first.name == second.name
+    {
+        second.name.push_str(" 2");
+    }
+    options
+}
+
+fn current_engine<'a>(engines: &'a mut [EngineConfig], flag: &str) -> &'a mut EngineConfig {
+    engines
+        .last_mut()
+        .unwrap_or_else(|| usage(&format!("{} must follow --engine", flag)))
+}
+
+fn number<T: std::str::FromStr>(text: &str, flag: &str) -> T {
+    text.parse()
+        .unwrap_or_else(|_| usage(&format!("invalid value '{}' for {}", text, flag)))
+}
+
+/// Comma-separated integers, e.g. "600,3".
+fn numbers(text: &str, flag: &str, count: std::ops::RangeInclusive<usize>) -> Vec<i64> {
+    let values: Vec<i64> = text.split(',').map(|v| number(v, flag)).collect();
+    if !count.contains(&values.len()) {
+        usage(&format!("invalid value '{}' for {}", text, flag));
+    }
+    values
+}
+
+/// Positions from an EPD file, or the positions at the end of each game of
+/// a PGN file.
+fn read_openings(path: &str) -> Vec<Board> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let mut openings = Vec::new();
+    if path.to_ascii_lowercase().ends_with(".epd") {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Board::from_epd(line) {
+                Ok(board) => openings.push(board),
+                Err(e) => {
+                    eprintln!("{}:{}: {}", path, number + 1, e);
+                    process::exit(1);
+                }
+            }
+        }
+    } else {
+        for game in PgnReader::new(&text) {
+            match game {
+                Ok(game) => openings.push(game.board),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+    if openings.is_empty() {
+        eprintln!("no openings found in {}", path);
+        process::exit(1);
+    }
+    openings
+}
+
+fn usage(problem: &str) -> ! {
+    eprintln!("{}", problem);
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,141 @@
+//! A scripted UCI engine for tests. Each argument is one line of script:
+//!
+//! - `on <command>` starts the responses to commands that begin with
+//!   `<command>`. When several sections match, the longest wins. Sections
+//!   for the same command are used in turn, and the last one repeats.
+//! - `sleep <ms>` pauses, `exit <code>` quits on the spot and `echo`
+//!   answers with "info string" and the command received. `history`
+//!   does the same for every command received so far, in order.
+//! - `exit-once <path> <code>` quits unless `<path>` exists, creating it
+//!   first, so that only the first of several processes crashes there.
+//! - Any other line is printed as it is. Lines before the first `on` are
+//!   printed at startup.
+//!
+//! Without a matching section, "uci" gets "id name Mock" and "uciok",
+//! "isready" gets "readyok", "quit" exits and anything else is ignored.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+struct Script {
+    startup: Vec<String>,
+    sections: Vec<(String, Vec<String>)>,
+}
+
+impl Script {
+    fn parse(lines: Vec<String>) -> Self {
+        let mut script = Script {
+            startup: Vec::new(),
+            sections: Vec::new(),
+        };
+        for line in lines {
+            if let Some(command) = line.strip_prefix("on ") {
+                script
+                    .sections
+                    .push((command.trim().to_string(), Vec::new()));
+            } else if let Some((_, responses)) = script.sections.last_mut() {
+                responses.push(line);
+            } else {
+                script.startup.push(line);
+            }
+        }
+        script
+    }
+
+    /// The command of the sections that answer `received`, if any.
+    fn best_match(&self, received: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .map(|(command, _)| command.as_str())
+            .filter(|command| {
+                received == *command
+                    || received
+                        .strip_prefix(command)
+                        .is_some_and(|rest| rest.starts_with(' '))
+            })
+            .max_by_key(|command| command.len())
+    }
+}
+
+fn main() {
+    let script = Script::parse(std::env::args().skip(1).collect());
+    let mut out = io::stdout().lock();
+    let mut received_so_far = Vec::new();
+    run(&script.startup, &received_so_far, &mut out);
+
+    // How many times each command's sections have been used.
+    let mut used: HashMap<&str, usize> = HashMap::new();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let received = line.trim();
+        received_so_far.push(received.to_string());
+        let Some(command) = script.best_match(received) else {
+            match received {
+                "uci" => respond(&mut out, &["id name Mock", "uciok"]),
+                "isready" => respond(&mut out, &["readyok"]),
+                "quit" => process::exit(0),
+                _ => {}
+            }
+            continue;
+        };
+
+        let sections: Vec<&Vec<String>> = script
+            .sections
+            .iter()
+            .filter(|(c, _)| c == command)
+            .map(|(_, responses)| responses)
+            .collect();
+        let count = used.entry(command).or_default();
+        let responses = sections[(*count).min(sections.len() - 1)];
+        *count += 1;
+        run(responses, &received_so_far, &mut out);
+    }
+}
+
+/// Plays `responses` to the last of the commands `received`.
+fn run(responses: &[String], received: &[String], out: &mut impl Write) {
+    for response in responses {
+        let (directive, argument) = response.split_once(' ').unwrap_or((response, ""));
+        match directive {
+            "sleep" => thread::sleep(Duration::from_millis(argument.parse().unwrap_or(0))),
+            "exit" => {
+                let _ = out.flush();
+                process::exit(argument.parse().unwrap_or(1));
+            }
+            "exit-once" => {
+                let (path, code) = argument.split_once(' ').unwrap_or((argument, "1"));
+                if fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(path)
+                    .is_ok()
+                {
+                    let _ = out.flush();
+                    process::exit(code.parse().unwrap_or(1));
+                }
+            }
+            "echo" => {
+                if let Some(command) = received.last() {
+                    respond(out, &[&format!("info string {}", command)]);
+                }
+            }
+            "history" => {
+                for command in received {
+                    respond(out, &[&format!("info string {}", command)]);
+                }
+            }
+            _ => respond(out, &[response]),
+        }
+    }
+}
+
+fn respond(out: &mut impl Write, lines: &[&str]) {
+    for line in lines {
+        let _ = writeln!(out, "{}", line);
+    }
+    let _ = out.flush();
+}
@@ -0,0 +1,49 @@
+use chess_rust::fen::STARTING_FEN;
+use chess_rust::game::Board;
+use std::env;
+use std::process;
+use std::time::Instant;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let depth = match args.first().and_then(|d| d.parse::<u32>().ok()) {
+        Some(d) => d,
+        None => {
+            eprintln!("usage: perft <depth> [fen]");
+            process::exit(2);
+        }
+    };
+    let fen = if args.len() > 1 {
+        args[1..].join(" ")
+    } else {
+        STARTING_FEN.to_string()
+    };
+
+    let board = match Board::from_fen(&fen) {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("invalid FEN: {}", e);
+            process::exit(2);
+        }
+    };
+
+    let start = Instant::now();
+    let mut total = 0;
+    for (mv, nodes) in board.perft_divide(depth) {
+        println!("{}: {}", mv, nodes);
+        total += nodes;
+    }
+    if depth == 0 {
+        // No root moves to divide by; the position itself is the one leaf.
+        total = board.perft(0);
+    }
+    let elapsed = start.elapsed();
+
+    println!();
+    println!("Nodes: {}", total);
+    println!(
+        "Time: {:.3}s ({:.0} nps)",
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64().max(1e-9)
+    );
+}
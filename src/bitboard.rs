@@ -0,0 +1,160 @@
+use crate::types::{Color, Square};
+
+/// One bit per square, a1 = bit 0, b1 = bit 1, ..., h8 = bit 63.
+pub type Bitboard = u64;
+
+pub const LIGHT_SQUARES: Bitboard = 0x55AA_55AA_55AA_55AA;
+
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_table(&[
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+]);
+
+pub const KING_ATTACKS: [Bitboard; 64] = leaper_table(&[
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+]);
+
+/// Squares a pawn of the given colour attacks from each square.
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    leaper_table(&[(-1, 1), (1, 1)]),
+    leaper_table(&[(-1, -1), (1, -1)]),
+];
+
+/// (file, rank) steps; the first four walk towards higher bit indices.
+const DIRECTIONS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 0),
+    (1, 1),
+    (-1, 1),
+    (0, -1),
+    (-1, 0),
+    (-1, -1),
+    (1, -1),
+];
+
+const ROOK_DIRECTIONS: [usize; 4] = [0, 1, 4, 5];
+const BISHOP_DIRECTIONS: [usize; 4] = [2, 3, 6, 7];
+
+/// Every square reachable from a square in one direction on an empty board.
+const RAYS: [[Bitboard; 64]; 8] = ray_tables();
+
+pub fn index(sq: Square) -> usize {
+    sq.1 as usize * 8 + sq.0 as usize
+}
+
+pub fn square(index: u32) -> Square {
+    Square((index % 8) as u8, (index / 8) as u8)
+}
+
+pub fn bit(sq: Square) -> Bitboard {
+    1 << index(sq)
+}
+
+/// Iterates over the squares of the set bits, lowest first.
+pub fn squares(mut bb: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bb == 0 {
+            return None;
+        }
+        let sq = square(bb.trailing_zeros());
+        bb &= bb - 1;
+        Some(sq)
+    })
+}
+
+pub fn pawn_attacks(color: Color, sq: Square) -> Bitboard {
+    PAWN_ATTACKS[color as usize][index(sq)]
+}
+
+pub fn knight_attacks(sq: Square) -> Bitboard {
+    KNIGHT_ATTACKS[index(sq)]
+}
+
+pub fn king_attacks(sq: Square) -> Bitboard {
+    KING_ATTACKS[index(sq)]
+}
+
+pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    ROOK_DIRECTIONS
+        .iter()
+        .fold(0, |acc, &dir| acc | ray_attacks(dir, index(sq), occupied))
+}
+
+pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    BISHOP_DIRECTIONS
+        .iter()
+        .fold(0, |acc, &dir| acc | ray_attacks(dir, index(sq), occupied))
+}
+
+pub fn queen_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(sq, occupied) | bishop_attacks(sq, occupied)
+}
+
+/// The ray up to and including the first occupied square.
+fn ray_attacks(dir: usize, from: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[dir][from];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let first = if dir < 4 {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[dir][first as usize]
+}
+
+const fn leaper_table(deltas: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let file = (sq % 8) as i8;
+        let rank = (sq / 8) as i8;
+        let mut i = 0;
+        while i < deltas.len() {
+            let f = file + deltas[i].0;
+            let r = rank + deltas[i].1;
+            if f >= 0 && f < 8 && r >= 0 && r < 8 {
+                table[sq] |= 1 << (r * 8 + f);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+const fn ray_tables() -> [[Bitboard; 64]; 8] {
+    let mut rays = [[0; 64]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let (df, dr) = DIRECTIONS[dir];
+        let mut sq = 0;
+        while sq < 64 {
+            let mut f = (sq % 8) as i8 + df;
+            let mut r = (sq / 8) as i8 + dr;
+            while f >= 0 && f < 8 && r >= 0 && r < 8 {
+                rays[dir][sq] |= 1 << (r * 8 + f);
+                f += df;
+                r += dr;
+            }
+            sq += 1;
+        }
+        dir += 1;
+    }
+    rays
+}
@@ -1,62 +1,1081 @@
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Command, Stdio};
+//! A client for chess engines that speak UCI, such as Stockfish. An
+//! [`Engine`] runs the engine as a child process and turns its output into
+//! [`EngineEvent`]s. Searches are described by [`SearchLimits`] and report
+//! [`SearchInfo`] as they go; anything that can fail returns an
+//! [`EngineError`].
+//!
+//! Only built with the `engine` feature, which is on by default. The
+//! feature adds no dependencies: it only hides this module and the players
+//! and match runner built on it, for users of the board code who do not
+//! want a library that spawns processes and threads.
+//!
+//! ```no_run
+//! use chess_rust::engine::{Engine, SearchLimits};
+//! use chess_rust::game::Board;
+//!
+//! let mut engine = Engine::spawn("stockfish", &[])?;
+//! engine.set_option("Hash", "64")?;
+//! engine.new_game()?;
+//! let result = engine.search(&Board::new(), &SearchLimits::depth(12), |info| {
+//!     if let (Some(depth), Some(score)) = (info.depth, info.score) {
+//!         println!("depth {}: {}", depth, score);
+//!     }
+//! })?;
+//! println!("best move: {:?}", result.best);
+//! # Ok::<(), chess_rust::engine::EngineError>(())
+//! ```
 
+use crate::fen::STARTING_FEN;
+use crate::game::Board;
+use crate::types::Move;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Neg;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long an engine gets to answer "uci" with "uciok".
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an engine gets to answer "isready" with "readyok". Generous,
+/// because engines may be resizing their hash table when asked.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long an engine gets to answer "stop" with "bestmove".
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How far past its move time a search may run before it is stopped.
+const MOVETIME_GRACE: Duration = Duration::from_secs(1);
+
+/// How long a search without a move time may run before it is stopped,
+/// unless changed with [`Engine::set_search_timeout`]. Generous, so that
+/// only an engine that hangs runs into it.
+pub const SEARCH_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How long an engine gets to exit after "quit" before it is killed.
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Why talking to an engine failed. New variants may be added, so matches
+/// need a catch-all arm.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EngineError {
+    /// No executable at the given path.
+    NotFound(String),
+    /// The process could not be started or talked to.
+    Io(String),
+    /// No reply within the allotted time.
+    Timeout(Duration),
+    /// The engine said something a UCI engine must not say at that point.
+    Protocol(String),
+    /// The process exited; the exit code, if it had one.
+    Exited(Option<i32>),
+    /// The engine does not advertise an option by this name.
+    UnknownOption(String),
+    /// The value does not fit the option's type or range.
+    InvalidOptionValue { name: String, value: String },
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::NotFound(path) => write!(f, "engine '{}' not found", path),
+            EngineError::Io(e) => write!(f, "engine I/O error: {}", e),
+            EngineError::Timeout(t) => write!(f, "engine did not answer within {:?}", t),
+            EngineError::Protocol(what) => write!(f, "engine protocol violation: {}", what),
+            EngineError::Exited(Some(code)) => write!(f, "engine exited with code {}", code),
+            EngineError::Exited(None) => write!(f, "engine was terminated"),
+            EngineError::UnknownOption(name) => write!(f, "engine has no option '{}'", name),
+            EngineError::InvalidOptionValue { name, value } => {
+                write!(f, "'{}' is not a valid value for option '{}'", value, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl EngineError {
+    /// Whether the process died or stopped answering, so that only a
+    /// restart can bring it back.
+    pub fn is_crash(&self) -> bool {
+        matches!(
+            self,
+            EngineError::Exited(_) | EngineError::Io(_) | EngineError::Timeout(_)
+        )
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(e: io::Error) -> Self {
+        EngineError::Io(e.to_string())
+    }
+}
+
+/// One line of engine output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// "id name Stockfish 16" and the like, without the "id".
+    Id(String),
+    Option(EngineOption),
+    /// The end of the handshake.
+    UciOk,
+    /// The answer to "isready".
+    ReadyOk,
+    Info(SearchInfo),
+    /// The end of a search.
+    BestMove {
+        best: Option<String>,
+        ponder: Option<String>,
+    },
+    /// Anything else, e.g. a banner or a debug message.
+    Other(String),
+}
+
+impl EngineEvent {
+    /// Classifies a line of engine output; blank lines are skipped.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim_start().to_string();
+        let event = match keyword {
+            "" => return None,
+            "id" => EngineEvent::Id(rest),
+            "option" => match EngineOption::parse(&rest) {
+                Some(option) => EngineEvent::Option(option),
+                None => EngineEvent::Other(line.to_string()),
+            },
+            "uciok" => EngineEvent::UciOk,
+            "readyok" => EngineEvent::ReadyOk,
+            "info" => EngineEvent::Info(SearchInfo::parse(&rest)),
+            "bestmove" => {
+                let mut words = rest.split_whitespace();
+                let best = words.next().map(str::to_string);
+                let ponder = match (words.next(), words.next()) {
+                    (Some("ponder"), Some(mv)) => Some(mv.to_string()),
+                    _ => None,
+                };
+                EngineEvent::BestMove { best, ponder }
+            }
+            _ => EngineEvent::Other(line.to_string()),
+        };
+        Some(event)
+    }
+}
+
+/// The kind of value an option takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionType {
+    Check,
+    Spin,
+    Combo,
+    Button,
+    String,
+}
+
+/// An option the engine advertised with an "option" line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionType,
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    /// The allowed values of a combo option.
+    pub vars: Vec<String>,
+}
+
+impl EngineOption {
+    /// Parses the part of an "option" line after "option", e.g.
+    /// "name Skill Level type spin default 20 min 0 max 20". Names and
+    /// values may contain spaces, so each runs up to the next keyword.
+    pub fn parse(text: &str) -> Option<Self> {
+        const KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        for word in text.split_whitespace() {
+            match fields.last_mut() {
+                // "name" always runs up to "type", even if it contains keywords.
+                Some(("name", value)) if word != "type" => push_word(value, word),
+                _ if KEYWORDS.contains(&word) => fields.push((word, String::new())),
+                Some((_, value)) => push_word(value, word),
+                None => return None,
+            }
+        }
+
+        let field = |key: &str| fields.iter().find(|(k, _)| *k == key).map(|(_, v)| v);
+        let kind = match field("type")?.as_str() {
+            "check" => OptionType::Check,
+            "spin" => OptionType::Spin,
+            "combo" => OptionType::Combo,
+            "button" => OptionType::Button,
+            "string" => OptionType::String,
+            _ => return None,
+        };
+        let name = field("name").filter(|name| !name.is_empty())?.clone();
+        Some(EngineOption {
+            name,
+            kind,
+            default: field("default").cloned(),
+            min: field("min").and_then(|v| v.parse().ok()),
+            max: field("max").and_then(|v| v.parse().ok()),
+            vars: fields
+                .iter()
+                .filter(|(k, _)| *k == "var")
+                .map(|(_, v)| v.clone())
+                .collect(),
+        })
+    }
+
+    /// `value` moved into the option's range, for spin options.
+    pub fn clamp(&self, value: i64) -> i64 {
+        let value = self.min.map_or(value, |min| value.max(min));
+        self.max.map_or(value, |max| value.min(max))
+    }
+
+    /// Whether `value` is acceptable for "setoption". Buttons take none.
+    pub fn accepts(&self, value: &str) -> bool {
+        match self.kind {
+            OptionType::Check => value == "true" || value == "false",
+            OptionType::Spin => value.parse::<i64>().is_ok_and(|v| {
+                self.min.is_none_or(|min| v >= min) && self.max.is_none_or(|max| v <= max)
+            }),
+            OptionType::Combo => self.vars.iter().any(|var| var.eq_ignore_ascii_case(value)),
+            OptionType::Button => value.is_empty(),
+            OptionType::String => true,
+        }
+    }
+}
+
+fn push_word(value: &mut String, word: &str) {
+    if !value.is_empty() {
+        value.push(' ');
+    }
+    value.push_str(word);
+}
+
+/// The options an engine advertised during the handshake, in the order it
+/// listed them. Names are case-insensitive, as in UCI.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineOptions {
+    options: Vec<EngineOption>,
+}
+
+impl EngineOptions {
+    /// The option called `name`, in any case.
+    pub fn get(&self, name: &str) -> Option<&EngineOption> {
+        self.options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EngineOption> {
+        self.options.iter()
+    }
+
+    /// Adds an option, replacing any earlier one of the same name.
+    pub fn insert(&mut self, option: EngineOption) {
+        match self
+            .options
+            .iter_mut()
+            .find(|o| o.name.eq_ignore_ascii_case(&option.name))
+        {
+            Some(existing) => *existing = option,
+            None => self.options.push(option),
+        }
+    }
+
+    /// The option `name` if `value` is valid for it.
+    pub fn validate(&self, name: &str, value: &str) -> Result<&EngineOption, EngineError> {
+        let option = self
+            .get(name)
+            .ok_or_else(|| EngineError::UnknownOption(name.to_string()))?;
+        if !option.accepts(value) {
+            return Err(EngineError::InvalidOptionValue {
+                name: option.name.clone(),
+                value: value.to_string(),
+            });
+        }
+        Ok(option)
+    }
+}
+
+/// An evaluation from the engine's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    /// Centipawns.
+    Cp(i32),
+    /// Mate in this many moves; negative when the engine is being mated.
+    Mate(i32),
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Cp(cp) => {
+                let sign = if *cp < 0 { "-" } else { "+" };
+                write!(f, "{}{}.{:02}", sign, cp.abs() / 100, cp.abs() % 100)
+            }
+            Score::Mate(n) => write!(f, "#{}", n),
+        }
+    }
+}
+
+/// The same evaluation from the other side's point of view.
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        match self {
+            Score::Cp(cp) => Score::Cp(-cp),
+            Score::Mate(n) => Score::Mate(-n),
+        }
+    }
+}
+
+/// Whether a score is exact or only a bound from a failed search window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bound {
+    #[default]
+    Exact,
+    /// The true score is at least this ("lowerbound").
+    Lower,
+    /// The true score is at most this ("upperbound").
+    Upper,
+}
+
+/// The fields of an "info" line. Engines send only some of them at a time,
+/// so each is optional.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub score: Option<Score>,
+    pub bound: Bound,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    /// How long the engine has been searching.
+    pub time: Option<Duration>,
+    /// Hash table usage in permill.
+    pub hashfull: Option<u32>,
+    pub tbhits: Option<u64>,
+    /// Which line this is when the engine reports several.
+    pub multipv: Option<u32>,
+    /// The principal variation in UCI notation.
+    pub pv: Vec<String>,
+    /// Free-form text from "info string".
+    pub string: Option<String>,
+}
+
+const INFO_KEYWORDS: [&str; 18] = [
+    "depth",
+    "seldepth",
+    "time",
+    "nodes",
+    "pv",
+    "multipv",
+    "score",
+    "currmove",
+    "currmovenumber",
+    "hashfull",
+    "nps",
+    "tbhits",
+    "sbhits",
+    "cpuload",
+    "string",
+    "refutation",
+    "currline",
+    "wdl",
+];
+
+impl SearchInfo {
+    /// Parses the part of an "info" line after "info". Unknown fields and
+    /// values that fail to parse are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut info = SearchInfo::default();
+        let mut words = text.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            match word {
+                "depth" => info.depth = words.next().and_then(|w| w.parse().ok()),
+                "seldepth" => info.seldepth = words.next().and_then(|w| w.parse().ok()),
+                "nodes" => info.nodes = words.next().and_then(|w| w.parse().ok()),
+                "nps" => info.nps = words.next().and_then(|w| w.parse().ok()),
+                "hashfull" => info.hashfull = words.next().and_then(|w| w.parse().ok()),
+                "tbhits" => info.tbhits = words.next().and_then(|w| w.parse().ok()),
+                "multipv" => info.multipv = words.next().and_then(|w| w.parse().ok()),
+                "time" => {
+                    info.time = words
+                        .next()
+                        .and_then(|w| w.parse().ok())
+                        .map(Duration::from_millis)
+                }
+                "score" => {
+                    let value = words.next().zip(words.next());
+                    info.score = match value {
+                        Some(("cp", v)) => v.parse().ok().map(Score::Cp),
+                        Some(("mate", v)) => v.parse().ok().map(Score::Mate),
+                        _ => None,
+                    };
+                    info.bound = match words.peek() {
+                        Some(&"lowerbound") => Bound::Lower,
+                        Some(&"upperbound") => Bound::Upper,
+                        _ => Bound::Exact,
+                    };
+                    if info.bound != Bound::Exact {
+                        words.next();
+                    }
+                }
+                "pv" => {
+                    while let Some(mv) = words.next_if(|w| !INFO_KEYWORDS.contains(w)) {
+                        info.pv.push(mv.to_string());
+                    }
+                }
+                "string" => {
+                    info.string = Some(words.by_ref().collect::<Vec<_>>().join(" "));
+                }
+                _ => {}
+            }
+        }
+        info
+    }
+}
+
+/// When the engine should stop searching. Unset fields are left out of the
+/// "go" command; with nothing set at all the engine decides for itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    /// Moves until the next time control; unset means sudden death.
+    pub movestogo: Option<u32>,
+    /// Search for a mate in this many moves.
+    pub mate: Option<u32>,
+    /// Search until told to stop.
+    pub infinite: bool,
+    /// Search on the opponent's time; see [`Engine::ponder`].
+    pub ponder: bool,
+}
+
+impl SearchLimits {
+    /// Search this many plies deep.
+    pub fn depth(depth: u32) -> Self {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Search for this long.
+    pub fn movetime(movetime: Duration) -> Self {
+        SearchLimits {
+            movetime: Some(movetime),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// The UCI command, e.g. "go wtime 60000 btime 60000 winc 1000 binc 1000".
+    pub fn go_command(&self) -> String {
+        let mut cmd = String::from("go");
+        if self.ponder {
+            cmd.push_str(" ponder");
+        }
+        let ms = |d: Duration| d.as_millis() as u64;
+        let fields = [
+            ("wtime", self.wtime.map(ms)),
+            ("btime", self.btime.map(ms)),
+            ("winc", self.winc.map(ms)),
+            ("binc", self.binc.map(ms)),
+            ("movestogo", self.movestogo.map(u64::from)),
+            ("depth", self.depth.map(u64::from)),
+            ("nodes", self.nodes),
+            ("mate", self.mate.map(u64::from)),
+            ("movetime", self.movetime.map(ms)),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                cmd.push_str(&format!(" {} {}", name, value));
+            }
+        }
+        if self.infinite {
+            cmd.push_str(" infinite");
+        }
+        cmd
+    }
+}
+
+/// What a finished search produced.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchResult {
+    /// `None` if the engine sent a bare "bestmove".
+    pub best: Option<String>,
+    pub ponder: Option<String>,
+    /// The last scored info line for the main line.
+    pub info: SearchInfo,
+}
+
+/// A UCI engine running as a child process. A background thread reads its
+/// output and turns each line into an [`EngineEvent`]. Dropping the engine
+/// sends "quit" and reaps the process.
 pub struct Engine {
-    process: std::process::Child,
+    process: Child,
+    stdin: ChildStdin,
+    events: Receiver<EngineEvent>,
+    options: EngineOptions,
+    /// Whether the "Ponder" option is on.
+    ponder: bool,
+    /// The "position" command of the search running on the opponent's time.
+    pondering: Option<String>,
+    /// How the engine was started, for [`Engine::restart`].
+    path: String,
+    args: Vec<String>,
+    handshake_timeout: Duration,
+    /// The longest a search without a move time may run; see
+    /// [`Engine::set_search_timeout`].
+    search_timeout: Option<Duration>,
+    /// The options set so far, with their latest values.
+    settings: Vec<(String, String)>,
 }
 
 impl Engine {
-    pub fn new() -> Self {
-        let process = Command::new("stockfish")
+    /// Starts the engine at `path` and waits for the "uci"/"uciok"
+    /// handshake, giving up after [`HANDSHAKE_TIMEOUT`].
+    pub fn spawn(path: &str, args: &[&str]) -> Result<Self, EngineError> {
+        Self::spawn_with_timeout(path, args, HANDSHAKE_TIMEOUT)
+    }
+
+    /// [`Engine::spawn`] with a different time limit for the handshake.
+    pub fn spawn_with_timeout(
+        path: &str,
+        args: &[&str],
+        timeout: Duration,
+    ) -> Result<Self, EngineError> {
+        let mut process = Command::new(path)
+            .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(Stdio::null())
             .spawn()
-            .unwrap();
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => EngineError::NotFound(path.to_string()),
+                _ => EngineError::from(e),
+            })?;
+
+        let stdin = process.stdin.take().expect("stdin is piped");
+        let stdout = process.stdout.take().expect("stdout is piped");
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || read_events(stdout, tx));
+
+        let mut engine = Engine {
+            process,
+            stdin,
+            events,
+            options: EngineOptions::default(),
+            ponder: false,
+            pondering: None,
+            path: path.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            handshake_timeout: timeout,
+            search_timeout: Some(SEARCH_TIMEOUT),
+            settings: Vec::new(),
+        };
+        engine.handshake(timeout)?;
+        Ok(engine)
+    }
+
+    /// Replaces the process with a fresh one started the same way, e.g.
+    /// after a crash, and sets the same options on it. Whatever position
+    /// the old process had must be sent again.
+    pub fn restart(&mut self) -> Result<(), EngineError> {
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        let mut fresh = Engine::spawn_with_timeout(&self.path, &args, self.handshake_timeout)?;
+        for (name, value) in &self.settings {
+            fresh.set_option(name, value)?;
+        }
+        fresh.wait_ready()?;
+        fresh.search_timeout = self.search_timeout;
+        // Dropping the old engine reaps it, or kills it if it hung.
+        *self = fresh;
+        Ok(())
+    }
+
+    /// Sends "uci" and waits for "uciok". Engines may print a banner and
+    /// any amount of `id` and `option` lines first, but no search output.
+    fn handshake(&mut self, timeout: Duration) -> Result<(), EngineError> {
+        self.send("uci")?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.recv_timeout(left) {
+                Ok(EngineEvent::UciOk) => return Ok(()),
+                Ok(EngineEvent::Option(option)) => self.options.insert(option),
+                Ok(EngineEvent::ReadyOk) => {
+                    return Err(EngineError::Protocol("readyok before uciok".to_string()));
+                }
+                Ok(EngineEvent::BestMove { .. }) => {
+                    return Err(EngineError::Protocol("bestmove before uciok".to_string()));
+                }
+                Ok(_) => {}
+                Err(EngineError::Timeout(_)) => return Err(EngineError::Timeout(timeout)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends one raw UCI command, for anything the methods here do not
+    /// cover. Replies arrive through [`Engine::recv`] and the like.
+    pub fn send(&mut self, cmd: &str) -> Result<(), EngineError> {
+        let written = writeln!(self.stdin, "{}", cmd).and_then(|()| self.stdin.flush());
+        match written {
+            Ok(()) => Ok(()),
+            Err(e) => Err(self.exited().unwrap_or(e.into())),
+        }
+    }
+
+    /// The options the engine advertised.
+    pub fn options(&self) -> &EngineOptions {
+        &self.options
+    }
+
+    /// Sends "setoption" after checking `value` against the option's type
+    /// and range. Use an empty value for a button.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), EngineError> {
+        let option = self.options.validate(name, value)?;
+        let cmd = match option.kind {
+            OptionType::Button => format!("setoption name {}", option.name),
+            _ => format!("setoption name {} value {}", option.name, value),
+        };
+        let name = option.name.clone();
+        let is_button = option.kind == OptionType::Button;
+        self.stop_pondering()?;
+        self.send(&cmd)?;
+        if name.eq_ignore_ascii_case("Ponder") {
+            self.ponder = value == "true";
+        }
+        if !is_button {
+            self.settings.retain(|(set, _)| *set != name);
+            self.settings.push((name, value.to_string()));
+        }
+        Ok(())
+    }
+
+    /// How long a search without a move time may run before the engine is
+    /// told to stop, [`SEARCH_TIMEOUT`] unless set here. `None` waits for
+    /// as long as the engine takes. Infinite and ponder searches only end
+    /// on "stop" and are never cut short.
+    pub fn set_search_timeout(&mut self, timeout: Option<Duration>) {
+        self.search_timeout = timeout;
+    }
+
+    /// Whether the "Ponder" option has been switched on, so the engine
+    /// expects to think on its opponent's time.
+    pub fn ponder_enabled(&self) -> bool {
+        self.ponder
+    }
+
+    /// Sends "isready" and waits for "readyok", dropping anything the
+    /// engine says in between.
+    pub fn wait_ready(&mut self) -> Result<(), EngineError> {
+        self.send("isready")?;
+        let deadline = Instant::now() + READY_TIMEOUT;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.recv_timeout(left) {
+                Ok(EngineEvent::ReadyOk) => return Ok(()),
+                Ok(_) => {}
+                Err(EngineError::Timeout(_)) => return Err(EngineError::Timeout(READY_TIMEOUT)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The next event if one is waiting, without blocking.
+    pub fn try_recv(&mut self) -> Result<Option<EngineEvent>, EngineError> {
+        match self.events.try_recv() {
+            Ok(event) => Ok(Some(event)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(self.closed()),
+        }
+    }
+
+    /// Blocks until the next event arrives or `timeout` passes.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<EngineEvent, EngineError> {
+        match self.events.recv_timeout(timeout) {
+            Ok(event) => Ok(event),
+            Err(RecvTimeoutError::Timeout) => Err(EngineError::Timeout(timeout)),
+            Err(RecvTimeoutError::Disconnected) => Err(self.closed()),
+        }
+    }
+
+    /// Blocks until the next event arrives.
+    pub fn recv(&mut self) -> Result<EngineEvent, EngineError> {
+        match self.events.recv() {
+            Ok(event) => Ok(event),
+            Err(_) => Err(self.closed()),
+        }
+    }
+
+    /// The best move at depth 12, without any of the search output.
+    #[allow(non_snake_case)]
+    pub fn bestMove(&mut self, board: &Board) -> Result<Option<String>, EngineError> {
+        self.search(board, &SearchLimits::depth(12), |_| {})
+            .map(|result| result.best)
+    }
 
-        let mut engine = Engine { process };
-        engine.send("uci");
-        engine.readUntil("uciok");
-        engine
+    /// Tells the engine that the next search is from a different game, so
+    /// it can clear its hash table, and waits until it has done so.
+    pub fn new_game(&mut self) -> Result<(), EngineError> {
+        self.stop_pondering()?;
+        self.send("ucinewgame")?;
+        self.wait_ready()
     }
 
-    pub fn send(&mut self, cmd: &str) {
-        let stdin = self.process.stdin.as_mut().unwrap();
-        stdin.write_all(cmd.as_bytes()).unwrap();
-        stdin.write_all(b"\n").unwrap();
-        stdin.flush().unwrap();
+    /// Sends the position the board was set up with and the moves played
+    /// since, so the engine sees the game history as well as the position.
+    pub fn set_position(&mut self, board: &Board) -> Result<(), EngineError> {
+        let (start, moves) = board.rewind();
+        self.set_position_fen(&start.to_fen(), &moves)
     }
 
-    pub fn readLine(&mut self) -> String {
-        let stdout = self.process.stdout.as_mut().unwrap();
-        let mut reader = BufReader::new(stdout);
-        let mut buf = String::new();
-        reader.read_line(&mut buf).unwrap();
-        buf
+    /// Sends `position fen <fen> moves ...`, or `position startpos ...` for
+    /// the standard starting position.
+    pub fn set_position_fen(&mut self, fen: &str, moves: &[Move]) -> Result<(), EngineError> {
+        self.stop_pondering()?;
+        self.send(&position_command(fen, moves))
+    }
+
+    /// Searches the current position of `board`; see [`Engine::go`]. If
+    /// the engine was pondering on exactly this position it is told
+    /// "ponderhit" and carries on with the search it already started.
+    pub fn search(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        on_info: impl FnMut(&SearchInfo),
+    ) -> Result<SearchResult, EngineError> {
+        let (start, moves) = board.rewind();
+        let fen = start.to_fen();
+        if self.pondering.as_deref() == Some(position_command(&fen, &moves).as_str()) {
+            self.pondering = None;
+            self.send("ponderhit")?;
+            return self.collect(limits, on_info);
+        }
+        self.set_position_fen(&fen, &moves)?;
+        self.go(limits, on_info)
     }
 
-    pub fn readUntil(&mut self, token: &str) {
+    /// Starts searching the position after the opponent replies to `board`
+    /// with `predicted`, the ponder move of the last search. The next
+    /// [`Engine::search`] picks this up if the prediction came true and
+    /// stops it otherwise.
+    pub fn ponder(
+        &mut self,
+        board: &Board,
+        predicted: Move,
+        limits: &SearchLimits,
+    ) -> Result<(), EngineError> {
+        let (start, mut moves) = board.rewind();
+        moves.push(predicted);
+        let fen = start.to_fen();
+        self.set_position_fen(&fen, &moves)?;
+        let limits = SearchLimits {
+            ponder: true,
+            ..limits.clone()
+        };
+        self.send(&limits.go_command())?;
+        self.pondering = Some(position_command(&fen, &moves));
+        Ok(())
+    }
+
+    /// Ends a search on the opponent's time, if there is one, and throws
+    /// away its result.
+    pub fn stop_pondering(&mut self) -> Result<(), EngineError> {
+        if self.pondering.take().is_none() {
+            return Ok(());
+        }
+        self.send("stop")?;
+        let deadline = Instant::now() + STOP_TIMEOUT;
         loop {
-            let line = self.readLine();
-            if line.contains(token) {
-                break;
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.recv_timeout(left) {
+                Ok(EngineEvent::BestMove { .. }) => return Ok(()),
+                Ok(_) => {}
+                Err(EngineError::Timeout(_)) => return Err(EngineError::Timeout(STOP_TIMEOUT)),
+                Err(e) => return Err(e),
             }
         }
     }
 
-    pub fn bestMove(&mut self, moves: &str) -> Option<String> {
-        self.send(&format!("position startpos moves {}", moves));
-        self.send("go depth 12");
+    /// Searches the last position sent, calling `on_info` for every info
+    /// line until the engine reports its best move. With `limits.infinite`
+    /// that only happens after "stop", so this does not return on its own.
+    pub fn go(
+        &mut self,
+        limits: &SearchLimits,
+        on_info: impl FnMut(&SearchInfo),
+    ) -> Result<SearchResult, EngineError> {
+        self.send(&limits.go_command())?;
+        self.collect(limits, on_info)
+    }
 
+    /// Reads search output up to "bestmove". An engine that overruns its
+    /// fixed move time, or the search timeout without one, is told to stop,
+    /// and then has [`STOP_TIMEOUT`] to answer.
+    fn collect(
+        &mut self,
+        limits: &SearchLimits,
+        mut on_info: impl FnMut(&SearchInfo),
+    ) -> Result<SearchResult, EngineError> {
+        let mut last = SearchInfo::default();
+        let budget = match limits.movetime {
+            _ if limits.infinite || limits.ponder => None,
+            Some(movetime) => Some(movetime + MOVETIME_GRACE),
+            None => self.search_timeout,
+        };
+        let mut deadline = budget.map(|budget| Instant::now() + budget);
+        let mut stopped = false;
         loop {
-            let line = self.readLine();
-            if line.starts_with("bestmove") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    return Some(parts[1].to_string());
-                } else {
-                    return None;
+            let event = match deadline {
+                None => self.recv()?,
+                Some(at) => match self.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                    Err(EngineError::Timeout(_)) if !stopped => {
+                        self.send("stop")?;
+                        stopped = true;
+                        deadline = Some(Instant::now() + STOP_TIMEOUT);
+                        continue;
+                    }
+                    Err(EngineError::Timeout(_)) => return Err(EngineError::Timeout(STOP_TIMEOUT)),
+                    event => event?,
+                },
+            };
+            match event {
+                EngineEvent::Info(info) => {
+                    on_info(&info);
+                    if info.score.is_some() && info.multipv.unwrap_or(1) == 1 {
+                        last = info;
+                    }
+                }
+                EngineEvent::BestMove { best, ponder } => {
+                    return Ok(SearchResult {
+                        best,
+                        ponder,
+                        info: last,
+                    });
                 }
+                _ => {}
             }
         }
     }
+
+    /// Starts analysing the current position of `board` with up to `lines`
+    /// candidate lines, as many as the engine's "MultiPV" option allows.
+    /// Engines without one give one line.
+    pub fn analyze(
+        &mut self,
+        board: &Board,
+        lines: u32,
+        limits: &SearchLimits,
+    ) -> Result<Analysis<'_>, EngineError> {
+        let multipv = self.options.get("MultiPV").map(|option| {
+            let previous = self
+                .settings
+                .iter()
+                .find(|(name, _)| *name == option.name)
+                .map(|(_, value)| value.clone());
+            (option.clamp(lines.into()), previous)
+        });
+        if let Some((lines, _)) = multipv {
+            self.set_option("MultiPV", &lines.to_string())?;
+        }
+        self.set_position(board)?;
+        self.send(&limits.go_command())?;
+        Ok(Analysis {
+            engine: self,
+            lines: BTreeMap::new(),
+            result: None,
+            multipv: multipv.map(|(_, previous)| previous),
+        })
+    }
+
+    /// The engine closed its output, so it has exited or is about to.
+    fn closed(&mut self) -> EngineError {
+        self.exited().unwrap_or(EngineError::Exited(None))
+    }
+
+    /// `Exited` with the exit code if the process has quit, allowing it a
+    /// moment to finish.
+    fn exited(&mut self) -> Option<EngineError> {
+        for _ in 0..10 {
+            if let Ok(Some(status)) = self.process.try_wait() {
+                return Some(EngineError::Exited(status.code()));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+}
+
+/// "position startpos ..." for the standard starting position, otherwise
+/// "position fen <fen> ...".
+fn position_command(fen: &str, moves: &[Move]) -> String {
+    let mut cmd = if fen == STARTING_FEN {
+        "position startpos".to_string()
+    } else {
+        format!("position fen {}", fen)
+    };
+    if !moves.is_empty() {
+        cmd.push_str(" moves");
+        for mv in moves {
+            cmd.push_str(&format!(" {}", mv));
+        }
+    }
+    cmd
+}
+
+/// A running analysis that keeps the latest info for each of the engine's
+/// candidate lines. Call [`Analysis::poll`] or [`Analysis::wait`] to take in
+/// new output. Dropping it stops the search.
+pub struct Analysis<'a> {
+    engine: &'a mut Engine,
+    lines: BTreeMap<u32, SearchInfo>,
+    result: Option<SearchResult>,
+    /// Set while "MultiPV" needs putting back, to the value it had been
+    /// set to before, if any.
+    multipv: Option<Option<String>>,
+}
+
+impl Analysis<'_> {
+    /// The latest info for each line, best line first.
+    pub fn lines(&self) -> &BTreeMap<u32, SearchInfo> {
+        &self.lines
+    }
+
+    /// Whether the engine has finished searching, which only happens on its
+    /// own for a limited search.
+    pub fn is_finished(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Takes in whatever output is waiting; true if any line changed.
+    pub fn poll(&mut self) -> Result<bool, EngineError> {
+        let mut changed = false;
+        while self.result.is_none()
+            && let Some(event) = self.engine.try_recv()?
+        {
+            changed |= self.update(event);
+        }
+        Ok(changed)
+    }
+
+    /// Waits up to `timeout` for output, then takes in the rest of what is
+    /// waiting; true if any line changed.
+    pub fn wait(&mut self, timeout: Duration) -> Result<bool, EngineError> {
+        if self.result.is_some() {
+            return Ok(false);
+        }
+        let changed = match self.engine.recv_timeout(timeout) {
+            Ok(event) => self.update(event),
+            Err(EngineError::Timeout(_)) => false,
+            Err(e) => return Err(e),
+        };
+        Ok(self.poll()? || changed)
+    }
+
+    /// Sends "stop" unless the search is already over and returns what the
+    /// engine settled on.
+    pub fn stop(mut self) -> Result<SearchResult, EngineError> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<SearchResult, EngineError> {
+        if self.result.is_none() {
+            self.engine.send("stop")?;
+            let deadline = Instant::now() + STOP_TIMEOUT;
+            while self.result.is_none() {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let event = match self.engine.recv_timeout(left) {
+                    Err(EngineError::Timeout(_)) => return Err(EngineError::Timeout(STOP_TIMEOUT)),
+                    other => other?,
+                };
+                self.update(event);
+            }
+        }
+        match self.multipv.take() {
+            Some(Some(previous)) => self.engine.set_option("MultiPV", &previous)?,
+            Some(None) => {
+                // Back to the default, and nothing for a restart to repeat.
+                let default = self
+                    .engine
+                    .options
+                    .get("MultiPV")
+                    .and_then(|o| o.default.clone());
+                self.engine
+                    .set_option("MultiPV", default.as_deref().unwrap_or("1"))?;
+                self.engine
+                    .settings
+                    .retain(|(name, _)| !name.eq_ignore_ascii_case("MultiPV"));
+            }
+            None => {}
+        }
+        Ok(self.result.clone().unwrap_or_default())
+    }
+
+    fn update(&mut self, event: EngineEvent) -> bool {
+        match event {
+            EngineEvent::Info(info) if !info.pv.is_empty() => {
+                self.lines.insert(info.multipv.unwrap_or(1), info);
+                true
+            }
+            EngineEvent::BestMove { best, ponder } => {
+                let info = self.lines.get(&1).cloned().unwrap_or_default();
+                self.result = Some(SearchResult { best, ponder, info });
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Drop for Analysis<'_> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        if let Ok(None) = self.process.try_wait() {
+            let _ = writeln!(self.stdin, "quit").and_then(|()| self.stdin.flush());
+            let deadline = Instant::now() + QUIT_TIMEOUT;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = self.process.try_wait() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            let _ = self.process.kill();
+        }
+        let _ = self.process.wait();
+    }
+}
+
+/// Runs on the reader thread until the engine closes its output or the
+/// engine is dropped.
+fn read_events(stdout: ChildStdout, events: Sender<EngineEvent>) {
+    for line in BufReader::new(stdout).split(b'\n') {
+        let Ok(line) = line else { break };
+        let Some(event) = EngineEvent::parse(&String::from_utf8_lossy(&line)) else {
+            continue;
+        };
+        if events.send(event).is_err() {
+            break;
+        }
+    }
 }
@@ -0,0 +1,305 @@
+use crate::game::{Board, opposite_color};
+use crate::types::{Color, Piece, PieceKind, Square};
+use std::fmt;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    /// A FEN needs 6 fields; the two move counters may be omitted.
+    FieldCount(usize),
+    RankCount(usize),
+    /// `rank` is 1-based, as written in the FEN.
+    RankLength {
+        rank: u8,
+        files: usize,
+    },
+    InvalidPiece(char),
+    PawnOnBackRank(Square),
+    KingCount {
+        color: Color,
+        count: usize,
+    },
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    /// A castling right without the king and that rook on their home
+    /// squares, e.g. 'K' without a king on e1 and a rook on h1.
+    CastlingWithoutPieces(char),
+    /// The side not to move is in check, so its king could be captured.
+    OpponentInCheck,
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::FieldCount(n) => write!(f, "expected 4 or 6 fields, found {}", n),
+            FenError::RankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::RankLength { rank, files } => {
+                write!(f, "rank {} describes {} files instead of 8", rank, files)
+            }
+            FenError::InvalidPiece(c) => write!(f, "invalid piece character '{}'", c),
+            FenError::PawnOnBackRank(sq) => write!(f, "pawn on back rank at {}", sq),
+            FenError::KingCount { color, count } => {
+                write!(f, "{:?} has {} kings, expected 1", color, count)
+            }
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling field '{}'", s),
+            FenError::CastlingWithoutPieces(c) => {
+                write!(f, "castling right '{}' without king and rook at home", c)
+            }
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant field '{}'", s),
+            FenError::InvalidHalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Board {
+    /// Reads the position from an EPD record: the first four FEN fields,
+    /// followed by operations such as "bm e4; id \"start\";", which are
+    /// ignored.
+    pub fn from_epd(epd: &str) -> Result<Board, FenError> {
+        let fields: Vec<&str> = epd.split_whitespace().take(4).collect();
+        if fields.len() != 4 {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+        Board::from_fen(&fields.join(" "))
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+
+        let mut board = Board::empty();
+        for (rank, row) in parse_placement(fields[0])?.iter().enumerate() {
+            for (file, piece) in row.iter().enumerate() {
+                board.set(Square(file as u8, rank as u8), *piece);
+            }
+        }
+
+        board.set_turn(match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            other => return Err(FenError::InvalidSideToMove(other.to_string())),
+        });
+
+        let mut rights = [false; 4];
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                let flag = match c {
+                    'K' => &mut rights[0],
+                    'Q' => &mut rights[1],
+                    'k' => &mut rights[2],
+                    'q' => &mut rights[3],
+                    _ => return Err(FenError::InvalidCastling(fields[2].to_string())),
+                };
+                if *flag {
+                    return Err(FenError::InvalidCastling(fields[2].to_string()));
+                }
+                *flag = true;
+            }
+        }
+        for (right, c) in rights.into_iter().zip(['K', 'Q', 'k', 'q']) {
+            if right && !has_castling_pieces(&board, c) {
+                return Err(FenError::CastlingWithoutPieces(c));
+            }
+        }
+        board.set_castling_rights(rights);
+
+        board.set_en_passant_target(if fields[3] == "-" {
+            None
+        } else {
+            let ep_rank = match board.turn() {
+                Color::White => 5,
+                Color::Black => 2,
+            };
+            match Square::parse(fields[3]) {
+                Some(sq) if sq.1 == ep_rank => Some(sq),
+                _ => return Err(FenError::InvalidEnPassant(fields[3].to_string())),
+            }
+        });
+
+        if fields.len() == 6 {
+            board.halfmove_clock = fields[4]
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            board.fullmove_number = match fields[5].parse() {
+                Ok(n) if n >= 1 => n,
+                _ => return Err(FenError::InvalidFullmoveNumber(fields[5].to_string())),
+            };
+        } else {
+            board.halfmove_clock = 0;
+            board.fullmove_number = 1;
+        }
+
+        if board.is_in_check(opposite_color(board.turn())) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        board.finish_setup();
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get(Square(file, rank)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.turn() {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        fen.push(' ');
+        let castling_len = fen.len();
+        for (right, c) in self.castling_rights().into_iter().zip(['K', 'Q', 'k', 'q']) {
+            if right {
+                fen.push(c);
+            }
+        }
+        if fen.len() == castling_len {
+            fen.push('-');
+        }
+
+        fen.push(' ');
+        match self.en_passant_target() {
+            Some(sq) => fen.push_str(&sq.to_string()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        fen
+    }
+}
+
+/// Whether the king and rook that castling right `c` ("KQkq") needs are on
+/// their home squares.
+fn has_castling_pieces(board: &Board, c: char) -> bool {
+    let (color, rank) = if c.is_ascii_uppercase() {
+        (Color::White, 0)
+    } else {
+        (Color::Black, 7)
+    };
+    let rook_file = if c.eq_ignore_ascii_case(&'k') { 7 } else { 0 };
+    let at = |file, kind| board.get(Square(file, rank)) == Some(Piece { kind, color });
+    at(4, PieceKind::King) && at(rook_file, PieceKind::Rook)
+}
+
+fn parse_placement(field: &str) -> Result<[[Option<Piece>; 8]; 8], FenError> {
+    let ranks: Vec<&str> = field.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+
+    let mut squares = [[None; 8]; 8];
+    let mut kings = [0, 0];
+
+    for (i, text) in ranks.iter().enumerate() {
+        let rank = 7 - i;
+        let mut file = 0usize;
+
+        for c in text.chars() {
+            if let Some(skip) = c.to_digit(10) {
+                if skip == 0 || skip > 8 {
+                    return Err(FenError::InvalidPiece(c));
+                }
+                file += skip as usize;
+                continue;
+            }
+
+            let piece = piece_from_char(c).ok_or(FenError::InvalidPiece(c))?;
+            if file < 8 {
+                let sq = Square(file as u8, rank as u8);
+                if piece.kind == PieceKind::Pawn && (rank == 0 || rank == 7) {
+                    return Err(FenError::PawnOnBackRank(sq));
+                }
+                if piece.kind == PieceKind::King {
+                    kings[piece.color as usize] += 1;
+                }
+                squares[rank][file] = Some(piece);
+            }
+            file += 1;
+        }
+
+        if file != 8 {
+            return Err(FenError::RankLength {
+                rank: rank as u8 + 1,
+                files: file,
+            });
+        }
+    }
+
+    for color in [Color::White, Color::Black] {
+        let count = kings[color as usize];
+        if count != 1 {
+            return Err(FenError::KingCount { color, count });
+        }
+    }
+
+    Ok(squares)
+}
+
+fn piece_from_char(c: char) -> Option<Piece> {
+    let kind = match c.to_ascii_lowercase() {
+        'p' => PieceKind::Pawn,
+        'n' => PieceKind::Knight,
+        'b' => PieceKind::Bishop,
+        'r' => PieceKind::Rook,
+        'q' => PieceKind::Queen,
+        'k' => PieceKind::King,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    Some(Piece { kind, color })
+}
+
+fn piece_to_char(piece: Piece) -> char {
+    let c = match piece.kind {
+        PieceKind::Pawn => 'p',
+        PieceKind::Knight => 'n',
+        PieceKind::Bishop => 'b',
+        PieceKind::Rook => 'r',
+        PieceKind::Queen => 'q',
+        PieceKind::King => 'k',
+    };
+    match piece.color {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
+    }
+}
@@ -1,33 +1,85 @@
+use crate::bitboard::{self, Bitboard};
 use crate::types::{Color, Move, Piece, PieceKind, Square};
+use crate::zobrist;
+
+const KINDS: [PieceKind; 6] = [
+    PieceKind::Pawn,
+    PieceKind::Rook,
+    PieceKind::Knight,
+    PieceKind::Bishop,
+    PieceKind::Queen,
+    PieceKind::King,
+];
 
 #[derive(Clone)]
 pub struct Board {
-    pub squares: [[Option<Piece>; 8]; 8],
-    pub turn: Color,
+    /// `pieces[color][kind]`, indexed by the enum discriminants.
+    pieces: [[Bitboard; 6]; 2],
+    occupied: [Bitboard; 2],
+    hash: u64,
+    // The hash depends on these, so they only change through the setters.
+    turn: Color,
+
+    white_kingside: bool,
+    white_queenside: bool,
+    black_kingside: bool,
+    black_queenside: bool,
 
-    pub white_kingside: bool,
-    pub white_queenside: bool,
-    pub black_kingside: bool,
-    pub black_queenside: bool,
+    en_passant_target: Option<Square>,
 
-    pub en_passant_target: Option<Square>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+
+    position_history: Vec<u64>,
+    undo_stack: Vec<Undo>,
 }
 
-impl Board {
-    pub fn new() -> Self {
-        Self {
-            squares: Self::starting_position(),
-            turn: Color::White,
-            white_kingside: true,
-            white_queenside: true,
-            black_kingside: true,
-            black_queenside: true,
-            en_passant_target: None,
-        }
+/// Everything `make_move` overwrites that can't be recomputed from the move.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Undo {
+    mv: Move,
+    moved: Piece,
+    captured: Option<(Square, Piece)>,
+    castling: [bool; 4],
+    en_passant_target: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    hash: u64,
+}
+
+/// How a game ended by the rules, as reported by [`Board::outcome`]. A
+/// claimed draw is `Draw` with a reason from [`Board::claimable_draw`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Checkmate { winner: Color },
+    Draw(DrawReason),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DrawReason {
+    Stalemate,
+    InsufficientMaterial,
+    /// Only claimable, so only from [`Board::claimable_draw`].
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    /// Only claimable, so only from [`Board::claimable_draw`].
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn starting_position() -> [[Option<Piece>; 8]; 8] {
-        let mut board = [[None; 8]; 8];
+impl Board {
+    pub fn new() -> Self {
+        let mut board = Self::empty();
+        board.white_kingside = true;
+        board.white_queenside = true;
+        board.black_kingside = true;
+        board.black_queenside = true;
 
         use Color::*;
         use PieceKind::*;
@@ -35,199 +87,293 @@ impl Board {
         let back_rank = [Rook, Knight, Bishop, Queen, King, Bishop, Knight, Rook];
 
         for (file, kind) in back_rank.iter().enumerate() {
-            board[0][file] = Some(Piece {
-                kind: *kind,
-                color: White,
-            });
-            board[1][file] = Some(Piece {
-                kind: Pawn,
-                color: White,
-            });
-        }
+            let file = file as u8;
+            board.set(
+                Square(file, 0),
+                Some(Piece {
+                    kind: *kind,
+                    color: White,
+                }),
+            );
+            board.set(
+                Square(file, 1),
+                Some(Piece {
+                    kind: Pawn,
+                    color: White,
+                }),
+            );
+            board.set(
+                Square(file, 7),
+                Some(Piece {
+                    kind: *kind,
+                    color: Black,
+                }),
+            );
+            board.set(
+                Square(file, 6),
+                Some(Piece {
+                    kind: Pawn,
+                    color: Black,
+                }),
+            );
+        }
+
+        board.finish_setup();
+        board
+    }
 
-        for (file, kind) in back_rank.iter().enumerate() {
-            board[7][file] = Some(Piece {
-                kind: *kind,
-                color: Black,
-            });
-            board[6][file] = Some(Piece {
-                kind: Pawn,
-                color: Black,
-            });
+    /// A board with no pieces and no castling rights, White to move.
+    pub fn empty() -> Self {
+        Self {
+            pieces: [[0; 6]; 2],
+            occupied: [0; 2],
+            hash: 0,
+            turn: Color::White,
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+            en_passant_target: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            position_history: Vec::new(),
+            undo_stack: Vec::new(),
         }
-
-        board
     }
 
     pub fn get(&self, sq: Square) -> Option<Piece> {
         if sq.0 > 7 || sq.1 > 7 {
             return None;
         }
-        self.squares[sq.1 as usize][sq.0 as usize]
+        let bit = bitboard::bit(sq);
+        let color = if self.occupied[Color::White as usize] & bit != 0 {
+            Color::White
+        } else if self.occupied[Color::Black as usize] & bit != 0 {
+            Color::Black
+        } else {
+            return None;
+        };
+        KINDS
+            .iter()
+            .find(|&&kind| self.pieces[color as usize][kind as usize] & bit != 0)
+            .map(|&kind| Piece { kind, color })
     }
 
     pub fn set(&mut self, sq: Square, piece: Option<Piece>) {
-        if sq.0 <= 7 && sq.1 <= 7 {
-            self.squares[sq.1 as usize][sq.0 as usize] = piece;
+        if sq.0 > 7 || sq.1 > 7 {
+            return;
         }
+        // Whether the en passant square counts depends on the pawns around
+        // it, so its key is taken out and put back with the new piece.
+        self.hash ^= self.en_passant_hash();
+        if let Some(old) = self.get(sq) {
+            self.remove(sq, old);
+        }
+        if let Some(p) = piece {
+            self.put(sq, p);
+        }
+        self.hash ^= self.en_passant_hash();
     }
 
-    fn apply_move(&mut self, mv: Move) {
-        let piece_opt = self.get(mv.from);
-        if piece_opt.is_none() {
-            return;
-        }
-        let piece = piece_opt.unwrap();
+    /// Zobrist key of the position: pieces, side to move, castling rights
+    /// and the en passant file when a capture there is possible.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
 
-        self.en_passant_target = None;
+    /// The side to move.
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
 
-        if piece.kind == PieceKind::King && (mv.from.1 == mv.to.1) {
-            let dx = mv.to.0 as i8 - mv.from.0 as i8;
-            if dx.abs() == 2 {
-                self.set(mv.from, None);
-                self.set(mv.to, Some(piece));
-
-                match piece.color {
-                    Color::White => {
-                        self.white_kingside = false;
-                        self.white_queenside = false;
-                        if dx > 0 {
-                            let rook_from = Square(7, 0);
-                            let rook_to = Square(5, 0);
-                            let rook = self.get(rook_from);
-                            self.set(rook_from, None);
-                            self.set(rook_to, rook);
-                        } else {
-                            let rook_from = Square(0, 0);
-                            let rook_to = Square(3, 0);
-                            let rook = self.get(rook_from);
-                            self.set(rook_from, None);
-                            self.set(rook_to, rook);
-                        }
-                    }
-                    Color::Black => {
-                        self.black_kingside = false;
-                        self.black_queenside = false;
-                        if dx > 0 {
-                            let rook_from = Square(7, 7);
-                            let rook_to = Square(5, 7);
-                            let rook = self.get(rook_from);
-                            self.set(rook_from, None);
-                            self.set(rook_to, rook);
-                        } else {
-                            let rook_from = Square(0, 7);
-                            let rook_to = Square(3, 7);
-                            let rook = self.get(rook_from);
-                            self.set(rook_from, None);
-                            self.set(rook_to, rook);
-                        }
-                    }
-                }
-                return;
-            }
+    pub fn set_turn(&mut self, turn: Color) {
+        if turn != self.turn {
+            // Whether the en passant square counts depends on who moves.
+            self.hash ^= self.en_passant_hash() ^ zobrist::side_to_move();
+            self.turn = turn;
+            self.hash ^= self.en_passant_hash();
         }
+    }
 
-        if piece.kind == PieceKind::Pawn {
-            let dx = mv.to.0 as i8 - mv.from.0 as i8;
-            let dy = mv.to.1 as i8 - mv.from.1 as i8;
-            let forward = match piece.color {
-                Color::White => 1,
-                Color::Black => -1,
-            };
+    /// Takes rights in the order of [`Board::castling_rights`].
+    pub fn set_castling_rights(&mut self, rights: [bool; 4]) {
+        self.hash ^= zobrist::castling(self.castling_rights()) ^ zobrist::castling(rights);
+        [
+            self.white_kingside,
+            self.white_queenside,
+            self.black_kingside,
+            self.black_queenside,
+        ] = rights;
+    }
 
-            if dx.abs() == 1 && dy == forward {
-                if let Some(ep) = self.en_passant_target {
-                    if ep.0 == mv.to.0 && ep.1 == mv.to.1 {
-                        let captured_pawn_sq = Square(mv.to.0, mv.from.1);
-                        self.set(captured_pawn_sq, None);
-                    }
-                }
-            }
+    /// The square a pawn that just moved two squares passed over.
+    pub fn en_passant_target(&self) -> Option<Square> {
+        self.en_passant_target
+    }
 
-            let last_rank = match piece.color {
-                Color::White => 7,
-                Color::Black => 0,
-            };
-            if mv.to.1 == last_rank {
-                self.set(mv.from, None);
-                self.set(
-                    mv.to,
-                    Some(Piece {
-                        kind: PieceKind::Queen,
-                        color: piece.color,
-                    }),
-                );
+    pub fn set_en_passant_target(&mut self, target: Option<Square>) {
+        self.hash ^= self.en_passant_hash();
+        self.en_passant_target = target;
+        self.hash ^= self.en_passant_hash();
+    }
 
-                return;
-            }
+    /// The squares holding `kind` pieces of `color`.
+    pub fn pieces(&self, color: Color, kind: PieceKind) -> Bitboard {
+        self.pieces[color as usize][kind as usize]
+    }
 
-            if dx == 0 {
-                if (mv.from.1 as i8 - mv.to.1 as i8).abs() == 2 {
-                    let ep_rank = ((mv.from.1 as i8 + mv.to.1 as i8) / 2) as u8;
-                    self.en_passant_target = Some(Square(mv.from.0, ep_rank));
-                }
+    pub fn occupied_by(&self, color: Color) -> Bitboard {
+        self.occupied[color as usize]
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.occupied[0] | self.occupied[1]
+    }
+
+    fn put(&mut self, sq: Square, piece: Piece) {
+        let bit = bitboard::bit(sq);
+        self.occupied[piece.color as usize] |= bit;
+        self.pieces[piece.color as usize][piece.kind as usize] |= bit;
+        self.hash ^= zobrist::piece(piece.color, piece.kind, sq);
+    }
+
+    fn remove(&mut self, sq: Square, piece: Piece) {
+        let bit = bitboard::bit(sq);
+        self.occupied[piece.color as usize] &= !bit;
+        self.pieces[piece.color as usize][piece.kind as usize] &= !bit;
+        self.hash ^= zobrist::piece(piece.color, piece.kind, sq);
+    }
+
+    /// Plays a move that is known to be legal and returns what it takes to
+    /// undo it. Clocks, castling rights and the side to move are updated.
+    pub(crate) fn apply_move(&mut self, mv: Move) -> Undo {
+        let moved = self
+            .get(mv.from)
+            .expect("apply_move called without a piece on the origin square");
+        let captured = match self.get(mv.to) {
+            Some(p) => Some((mv.to, p)),
+            None if moved.kind == PieceKind::Pawn && mv.from.0 != mv.to.0 => {
+                let sq = Square(mv.to.0, mv.from.1);
+                self.get(sq).map(|p| (sq, p))
             }
+            None => None,
+        };
+        let undo = Undo {
+            mv,
+            moved,
+            captured,
+            castling: self.castling_rights(),
+            en_passant_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+        };
+
+        // Take out the parts of the key that depend on more than the pieces;
+        // they go back in once the move is complete.
+        self.hash ^= self.en_passant_hash() ^ zobrist::castling(self.castling_rights());
+
+        if let Some((sq, piece)) = captured {
+            self.remove(sq, piece);
         }
+        self.remove(mv.from, moved);
+        let placed = match mv.promotion {
+            Some(kind) if moved.kind == PieceKind::Pawn => Piece {
+                kind,
+                color: moved.color,
+            },
+            _ => moved,
+        };
+        self.put(mv.to, placed);
 
-        if piece.kind == PieceKind::King {
-            match piece.color {
-                Color::White => {
+        if moved.kind == PieceKind::King && (mv.to.0 as i8 - mv.from.0 as i8).abs() == 2 {
+            let (rook_from, rook_to) = castling_rook_squares(mv);
+            let rook = Piece {
+                kind: PieceKind::Rook,
+                color: moved.color,
+            };
+            self.remove(rook_from, rook);
+            self.put(rook_to, rook);
+        }
+
+        // Anything leaving or landing on a king or rook home square loses
+        // the castling rights tied to it.
+        for sq in [mv.from, mv.to] {
+            match sq {
+                Square(4, 0) => {
                     self.white_kingside = false;
                     self.white_queenside = false;
                 }
-                Color::Black => {
+                Square(4, 7) => {
                     self.black_kingside = false;
                     self.black_queenside = false;
                 }
+                Square(0, 0) => self.white_queenside = false,
+                Square(7, 0) => self.white_kingside = false,
+                Square(0, 7) => self.black_queenside = false,
+                Square(7, 7) => self.black_kingside = false,
+                _ => {}
             }
         }
 
-        if piece.kind == PieceKind::Rook {
-            match piece.color {
-                Color::White => {
-                    if mv.from == Square(0, 0) {
-                        self.white_queenside = false;
-                    }
-                    if mv.from == Square(7, 0) {
-                        self.white_kingside = false;
-                    }
-                }
-                Color::Black => {
-                    if mv.from == Square(0, 7) {
-                        self.black_queenside = false;
-                    }
-                    if mv.from == Square(7, 7) {
-                        self.black_kingside = false;
-                    }
-                }
-            }
+        self.en_passant_target = None;
+        if moved.kind == PieceKind::Pawn && (mv.to.1 as i8 - mv.from.1 as i8).abs() == 2 {
+            let ep_rank = (mv.from.1 + mv.to.1) / 2;
+            self.en_passant_target = Some(Square(mv.from.0, ep_rank));
         }
 
-        if let Some(captured) = self.get(mv.to) {
-            if captured.kind == PieceKind::Rook {
-                match captured.color {
-                    Color::White => {
-                        if mv.to == Square(0, 0) {
-                            self.white_queenside = false;
-                        }
-                        if mv.to == Square(7, 0) {
-                            self.white_kingside = false;
-                        }
-                    }
-                    Color::Black => {
-                        if mv.to == Square(0, 7) {
-                            self.black_queenside = false;
-                        }
-                        if mv.to == Square(7, 7) {
-                            self.black_kingside = false;
-                        }
-                    }
-                }
-            }
+        if captured.is_some() || moved.kind == PieceKind::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
         }
+        if self.turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.turn = opposite_color(self.turn);
+
+        self.hash ^= zobrist::side_to_move()
+            ^ zobrist::castling(self.castling_rights())
+            ^ self.en_passant_hash();
+        debug_assert_eq!(self.hash, self.compute_hash(), "incremental hash drifted");
 
-        self.set(mv.from, None);
-        self.set(mv.to, Some(piece));
+        undo
+    }
+
+    /// Reverses `apply_move`.
+    pub(crate) fn revert_move(&mut self, undo: &Undo) {
+        let mv = undo.mv;
+        self.turn = opposite_color(self.turn);
+
+        if undo.moved.kind == PieceKind::King && (mv.to.0 as i8 - mv.from.0 as i8).abs() == 2 {
+            let (rook_from, rook_to) = castling_rook_squares(mv);
+            let rook = Piece {
+                kind: PieceKind::Rook,
+                color: undo.moved.color,
+            };
+            self.remove(rook_to, rook);
+            self.put(rook_from, rook);
+        }
+
+        let placed = self
+            .get(mv.to)
+            .expect("revert_move called for a move that was not applied");
+        self.remove(mv.to, placed);
+        self.put(mv.from, undo.moved);
+        if let Some((sq, piece)) = undo.captured {
+            self.put(sq, piece);
+        }
+
+        [
+            self.white_kingside,
+            self.white_queenside,
+            self.black_kingside,
+            self.black_queenside,
+        ] = undo.castling;
+        self.en_passant_target = undo.en_passant_target;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
     }
 
     pub fn make_move(&mut self, mv: Move) -> bool {
@@ -239,17 +385,29 @@ impl Board {
             return false;
         }
 
-        self.apply_move(mv);
+        let undo = self.apply_move(mv);
+        self.undo_stack.push(undo);
 
-        self.turn = match self.turn {
-            Color::White => Color::Black,
-            Color::Black => Color::White,
-        };
+        self.position_history.push(self.hash);
 
         true
     }
 
-    pub fn is_legal_move(&self, mv: Move, color: Color) -> bool {
+    /// Takes back the last move played with `make_move` and returns it.
+    pub fn unmake_move(&mut self) -> Option<Move> {
+        let undo = self.undo_stack.pop()?;
+        self.position_history.pop();
+        self.revert_move(&undo);
+        Some(undo.mv)
+    }
+
+    /// Moves played with `make_move` since this board was set up, oldest first.
+    pub fn played_moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.undo_stack.iter().map(|undo| undo.mv)
+    }
+
+    /// Follows the movement rules, but may leave the mover's king in check.
+    pub fn is_legal_move(&self, mv: Move, _color: Color) -> bool {
         if mv.from.0 > 7 || mv.from.1 > 7 || mv.to.0 > 7 || mv.to.1 > 7 {
             return false;
         }
@@ -263,29 +421,16 @@ impl Board {
             return false;
         }
 
-        if let Some(dest) = self.get(mv.to) {
-            if dest.color == piece.color {
-                return false;
-            }
-        }
-
-        match piece.kind {
-            PieceKind::Pawn => self.legal_pawn_move(piece.color, mv),
-            PieceKind::Knight => self.legal_knight_move(mv),
-            PieceKind::Bishop => self.legal_bishop_move(mv),
-            PieceKind::Rook => self.legal_rook_move(mv),
-            PieceKind::Queen => self.legal_queen_move(mv),
-            PieceKind::King => self.legal_king_move(mv),
-        }
+        let mut moves = Vec::new();
+        self.piece_moves(mv.from, piece, &mut moves);
+        moves.contains(&mv)
     }
 
     pub fn is_in_check(&self, color: Color) -> bool {
-        let king_sq = self.find_king(color);
-        if king_sq.is_none() {
-            return false;
+        match self.find_king(color) {
+            Some(king_sq) => self.is_square_attacked(king_sq, opposite_color(color)),
+            None => false,
         }
-        let king_sq = king_sq.unwrap();
-        self.is_square_attacked(king_sq, opposite_color(color))
     }
 
     pub fn is_checkmate(&self, color: Color) -> bool {
@@ -304,375 +449,411 @@ impl Board {
         moves.is_empty()
     }
 
-    pub fn generate_legal_moves(&self, color: Color) -> Vec<Move> {
-        let mut moves = Vec::new();
-
-        for y in 0..8 {
-            for x in 0..8 {
-                let from = Square(x as u8, y as u8);
-                if let Some(p) = self.get(from) {
-                    if p.color != color {
-                        continue;
-                    }
-
-                    for ty in 0..8 {
-                        for tx in 0..8 {
-                            let to = Square(tx as u8, ty as u8);
-                            let mv = Move { from, to };
-
-                            if !self.is_legal_move(mv, color) {
-                                continue;
-                            }
-                            if self.would_cause_self_check(mv, color) {
-                                continue;
-                            }
-
-                            moves.push(mv);
-                        }
-                    }
+    /// Checkmate and stalemate first, then the draws that end the game on
+    /// their own (dead position, fivefold repetition, 75-move rule). Draws
+    /// a player may claim instead come from [`Board::claimable_draw`].
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.generate_legal_moves(self.turn).is_empty() {
+            return Some(if self.is_in_check(self.turn) {
+                Outcome::Checkmate {
+                    winner: opposite_color(self.turn),
                 }
-            }
+            } else {
+                Outcome::Draw(DrawReason::Stalemate)
+            });
         }
 
-        moves
+        let reason = if self.is_insufficient_material() {
+            DrawReason::InsufficientMaterial
+        } else if self.repetition_count() >= 5 {
+            DrawReason::FivefoldRepetition
+        } else if self.halfmove_clock >= 150 {
+            DrawReason::SeventyFiveMoveRule
+        } else {
+            return None;
+        };
+        Some(Outcome::Draw(reason))
     }
 
-    pub fn is_square_attacked(&self, sq: Square, by_color: Color) -> bool {
-        for y in 0..8 {
-            for x in 0..8 {
-                let from = Square(x as u8, y as u8);
-                if let Some(p) = self.get(from) {
-                    if p.color != by_color {
-                        continue;
-                    }
-
-                    let mv = Move { from, to: sq };
-
-                    match p.kind {
-                        PieceKind::Pawn => {
-                            let dx = sq.0 as i8 - from.0 as i8;
-                            let dy = sq.1 as i8 - from.1 as i8;
-                            let forward = if by_color == Color::White { 1 } else { -1 };
-                            if dy == forward && dx.abs() == 1 {
-                                return true;
-                            }
-                        }
-                        PieceKind::Knight => {
-                            if self.legal_knight_move(mv) {
-                                return true;
-                            }
-                        }
-                        PieceKind::Bishop => {
-                            if (sq.0 as i8 - from.0 as i8).abs()
-                                == (sq.1 as i8 - from.1 as i8).abs()
-                            {
-                                if self.clear_diagonal(mv) {
-                                    return true;
-                                }
-                            }
-                        }
-                        PieceKind::Rook => {
-                            if sq.0 == from.0 || sq.1 == from.1 {
-                                if self.clear_straight(mv) {
-                                    return true;
-                                }
-                            }
-                        }
-                        PieceKind::Queen => {
-                            if (sq.0 == from.0 || sq.1 == from.1 && self.clear_straight(mv))
-                                || ((sq.0 as i8 - from.0 as i8).abs()
-                                    == (sq.1 as i8 - from.1 as i8).abs()
-                                    && self.clear_diagonal(mv))
-                            {
-                                return true;
-                            }
-                        }
-                        PieceKind::King => {
-                            let dx = (sq.0 as i8 - from.0 as i8).abs();
-                            let dy = (sq.1 as i8 - from.1 as i8).abs();
-                            if dx <= 1 && dy <= 1 {
-                                return true;
-                            }
-                        }
-                    }
-                }
-            }
+    /// A draw the player to move may claim, but need not: threefold
+    /// repetition or the fifty-move rule.
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.is_threefold_repetition() {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.is_fifty_move_draw() {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
         }
-        false
     }
 
-    //helpers
-    fn legal_pawn_move(&self, color: Color, mv: Move) -> bool {
-        let dx = mv.to.0 as i8 - mv.from.0 as i8;
-        let dy = mv.to.1 as i8 - mv.from.1 as i8;
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
 
-        let forward = match color {
-            Color::White => 1,
-            Color::Black => -1,
-        };
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
 
-        if dx == 0 && dy == forward {
-            return self.get(mv.to).is_none();
+    /// Neither side can mate: bare kings, a single minor piece, or only
+    /// bishops that all stand on squares of the same colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut bishops = 0;
+        let mut knights = 0;
+        for color in [Color::White, Color::Black] {
+            let heavy = self.pieces(color, PieceKind::Pawn)
+                | self.pieces(color, PieceKind::Rook)
+                | self.pieces(color, PieceKind::Queen);
+            if heavy != 0 {
+                return false;
+            }
+            bishops |= self.pieces(color, PieceKind::Bishop);
+            knights |= self.pieces(color, PieceKind::Knight);
         }
 
-        if dx == 0 && dy == forward * 2 {
-            let start_rank = match color {
-                Color::White => 1,
-                Color::Black => 6,
-            };
-            if mv.from.1 == start_rank {
-                let middle = Square(mv.from.0, (mv.from.1 as i8 + forward) as u8);
-                return self.get(middle).is_none() && self.get(mv.to).is_none();
+        let minors = (bishops | knights).count_ones();
+        let same_colored_bishops =
+            bishops & bitboard::LIGHT_SQUARES == 0 || bishops & !bitboard::LIGHT_SQUARES == 0;
+        minors <= 1 || (knights == 0 && same_colored_bishops)
+    }
+
+    /// How many times the current position has occurred, including now.
+    /// Only positions since the last capture or pawn move can repeat.
+    fn repetition_count(&self) -> usize {
+        let current = match self.position_history.last() {
+            Some(key) => key,
+            None => return 0,
+        };
+        self.position_history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize + 1)
+            .step_by(2)
+            .filter(|key| *key == current)
+            .count()
+    }
+
+    /// Recomputes derived state after the board was set up square by square.
+    pub(crate) fn finish_setup(&mut self) {
+        self.hash = self.compute_hash();
+        self.position_history = vec![self.hash];
+    }
+
+    fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for color in [Color::White, Color::Black] {
+            for kind in KINDS {
+                for sq in bitboard::squares(self.pieces(color, kind)) {
+                    hash ^= zobrist::piece(color, kind, sq);
+                }
             }
         }
+        if self.turn == Color::Black {
+            hash ^= zobrist::side_to_move();
+        }
+        hash ^ zobrist::castling(self.castling_rights()) ^ self.en_passant_hash()
+    }
 
-        if dy == forward && dx.abs() == 1 {
-            if self.get(mv.to).is_some() {
-                return true;
+    /// The en passant square only distinguishes positions when a pawn of
+    /// the side to move could actually capture there.
+    fn en_passant_hash(&self) -> u64 {
+        match self.en_passant_target {
+            Some(ep)
+                if bitboard::pawn_attacks(opposite_color(self.turn), ep)
+                    & self.pieces(self.turn, PieceKind::Pawn)
+                    != 0 =>
+            {
+                zobrist::en_passant_file(ep.0)
             }
+            _ => 0,
+        }
+    }
 
-            if let Some(ep) = self.en_passant_target {
-                if mv.to.0 == ep.0 && mv.to.1 == ep.1 {
-                    return true;
-                }
+    pub fn generate_legal_moves(&self, color: Color) -> Vec<Move> {
+        let mut moves = Vec::new();
+        if color != self.turn {
+            return moves;
+        }
+
+        for kind in KINDS {
+            for from in bitboard::squares(self.pieces(color, kind)) {
+                self.piece_moves(from, Piece { kind, color }, &mut moves);
             }
         }
 
-        false
+        moves.retain(|&mv| !self.would_cause_self_check(mv, color));
+        moves
     }
 
-    fn legal_knight_move(&self, mv: Move) -> bool {
-        let dx = (mv.to.0 as i8 - mv.from.0 as i8).abs();
-        let dy = (mv.to.1 as i8 - mv.from.1 as i8).abs();
-        (dx == 1 && dy == 2) || (dx == 2 && dy == 1)
-    }
+    /// Pseudo-legal moves of the piece standing on `from`.
+    fn piece_moves(&self, from: Square, piece: Piece, moves: &mut Vec<Move>) {
+        let own = self.occupied_by(piece.color);
+        let enemy = self.occupied_by(opposite_color(piece.color));
+        let occupied = own | enemy;
 
-    fn legal_bishop_move(&self, mv: Move) -> bool {
-        let dx = (mv.to.0 as i8 - mv.from.0 as i8).abs();
-        let dy = (mv.to.1 as i8 - mv.from.1 as i8).abs();
+        let targets = match piece.kind {
+            PieceKind::Pawn => {
+                self.pawn_moves(from, piece.color, moves);
+                return;
+            }
+            PieceKind::Knight => bitboard::knight_attacks(from),
+            PieceKind::Bishop => bitboard::bishop_attacks(from, occupied),
+            PieceKind::Rook => bitboard::rook_attacks(from, occupied),
+            PieceKind::Queen => bitboard::queen_attacks(from, occupied),
+            PieceKind::King => {
+                self.castling_moves(from, piece.color, moves);
+                bitboard::king_attacks(from)
+            }
+        };
 
-        if dx != dy {
-            return false;
+        for to in bitboard::squares(targets & !own) {
+            moves.push(Move {
+                from,
+                to,
+                promotion: None,
+            });
         }
-        self.clear_diagonal(mv)
     }
 
-    fn legal_rook_move(&self, mv: Move) -> bool {
-        let dx = mv.to.0 as i8 - mv.from.0 as i8;
-        let dy = mv.to.1 as i8 - mv.from.1 as i8;
+    fn pawn_moves(&self, from: Square, color: Color, moves: &mut Vec<Move>) {
+        let occupied = self.occupied();
+        let forward: i8 = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
 
-        if dx != 0 && dy != 0 {
-            return false;
+        let mut targets = 0;
+        let one = (from.1 as i8 + forward) as u8;
+        if one <= 7 && occupied & bitboard::bit(Square(from.0, one)) == 0 {
+            targets |= bitboard::bit(Square(from.0, one));
+            let start_rank = (home_rank(color) as i8 + forward) as u8;
+            let two = (one as i8 + forward) as u8;
+            if from.1 == start_rank && occupied & bitboard::bit(Square(from.0, two)) == 0 {
+                targets |= bitboard::bit(Square(from.0, two));
+            }
         }
-        self.clear_straight(mv)
-    }
 
-    fn legal_queen_move(&self, mv: Move) -> bool {
-        let dx = (mv.to.0 as i8 - mv.from.0 as i8).abs();
-        let dy = (mv.to.1 as i8 - mv.from.1 as i8).abs();
-        if dx == dy {
-            self.clear_diagonal(mv)
-        } else if dx == 0 || dy == 0 {
-            self.clear_straight(mv)
-        } else {
-            false
-        }
-    }
-
-    fn legal_king_move(&self, mv: Move) -> bool {
-        let dx = (mv.to.0 as i8 - mv.from.0 as i8).abs();
-        let dy = (mv.to.1 as i8 - mv.from.1 as i8).abs();
-        if dx <= 1 && dy <= 1 {
-            true
-        } else if dx == 2 && dy == 0 {
-            let can = match self.get(mv.from) {
-                Some(p) if p.kind == PieceKind::King => {
-                    if p.color == Color::White {
-                        self.can_castle_kingside(Color::White) && mv.to.0 == 6
-                            || self.can_castle_queenside(Color::White) && mv.to.0 == 2
-                    } else {
-                        self.can_castle_kingside(Color::Black) && mv.to.0 == 6
-                            || self.can_castle_queenside(Color::Black) && mv.to.0 == 2
-                    }
-                }
-                _ => false,
+        let mut capturable = self.occupied_by(opposite_color(color));
+        if let Some(ep) = self.en_passant_target {
+            capturable |= bitboard::bit(ep);
+        }
+        targets |= bitboard::pawn_attacks(color, from) & capturable;
+
+        for to in bitboard::squares(targets) {
+            let promotions: &[Option<PieceKind>] = if to.1 == last_rank(color) {
+                &PROMOTION_KINDS
+            } else {
+                &[None]
             };
-            can
-        } else {
-            false
+            for &promotion in promotions {
+                moves.push(Move {
+                    from,
+                    to,
+                    promotion,
+                });
+            }
         }
     }
 
-    fn clear_straight(&self, mv: Move) -> bool {
-        let (x1, y1) = (mv.from.0 as i8, mv.from.1 as i8);
-        let (x2, y2) = (mv.to.0 as i8, mv.to.1 as i8);
+    fn castling_moves(&self, from: Square, color: Color, moves: &mut Vec<Move>) {
+        let rank = home_rank(color);
+        if from != Square(4, rank) {
+            return;
+        }
+        if self.can_castle_kingside(color) {
+            moves.push(Move {
+                from,
+                to: Square(6, rank),
+                promotion: None,
+            });
+        }
+        if self.can_castle_queenside(color) {
+            moves.push(Move {
+                from,
+                to: Square(2, rank),
+                promotion: None,
+            });
+        }
+    }
 
-        let dx = (x2 - x1).signum();
-        let dy = (y2 - y1).signum();
+    pub fn is_square_attacked(&self, sq: Square, by_color: Color) -> bool {
+        self.attackers(sq, by_color, self.occupied()) != 0
+    }
 
-        let mut x = x1 + dx;
-        let mut y = y1 + dy;
+    /// Pieces of `by_color` attacking `sq`, with sliders seeing through
+    /// everything not in `occupied`.
+    fn attackers(&self, sq: Square, by_color: Color, occupied: Bitboard) -> Bitboard {
+        let pieces = |kind| self.pieces(by_color, kind) & occupied;
+        let diagonal = pieces(PieceKind::Bishop) | pieces(PieceKind::Queen);
+        let straight = pieces(PieceKind::Rook) | pieces(PieceKind::Queen);
+
+        (bitboard::pawn_attacks(opposite_color(by_color), sq) & pieces(PieceKind::Pawn))
+            | (bitboard::knight_attacks(sq) & pieces(PieceKind::Knight))
+            | (bitboard::king_attacks(sq) & pieces(PieceKind::King))
+            | (bitboard::bishop_attacks(sq, occupied) & diagonal)
+            | (bitboard::rook_attacks(sq, occupied) & straight)
+    }
 
-        loop {
-            if x == x2 && y == y2 {
-                break;
-            }
+    pub(crate) fn find_king(&self, color: Color) -> Option<Square> {
+        bitboard::squares(self.pieces(color, PieceKind::King)).next()
+    }
 
-            if self.get(Square(x as u8, y as u8)).is_some() {
-                return false;
+    /// Whether the pseudo-legal `mv` would leave `color`'s king attacked,
+    /// worked out on the occupancy after the move instead of on a copy.
+    fn would_cause_self_check(&self, mv: Move, color: Color) -> bool {
+        let moved = match self.get(mv.from) {
+            Some(p) => p,
+            None => return false,
+        };
+        let king_sq = if moved.kind == PieceKind::King {
+            mv.to
+        } else {
+            match self.find_king(color) {
+                Some(sq) => sq,
+                None => return false,
             }
+        };
 
-            x += dx;
-            y += dy;
+        let mut captured = bitboard::bit(mv.to);
+        if moved.kind == PieceKind::Pawn && mv.from.0 != mv.to.0 && self.get(mv.to).is_none() {
+            captured = bitboard::bit(Square(mv.to.0, mv.from.1));
         }
 
-        true
+        let occupied =
+            (self.occupied() & !bitboard::bit(mv.from) & !captured) | bitboard::bit(mv.to);
+        self.attackers(king_sq, opposite_color(color), occupied) & !captured != 0
     }
 
-    fn clear_diagonal(&self, mv: Move) -> bool {
-        let (x1, y1) = (mv.from.0 as i8, mv.from.1 as i8);
-        let (x2, y2) = (mv.to.0 as i8, mv.to.1 as i8);
+    fn can_castle_kingside(&self, color: Color) -> bool {
+        let (allowed, rank) = match color {
+            Color::White => (self.white_kingside, 0),
+            Color::Black => (self.black_kingside, 7),
+        };
+        allowed
+            && self.get(Square(5, rank)).is_none()
+            && self.get(Square(6, rank)).is_none()
+            && self.get(Square(7, rank))
+                == Some(Piece {
+                    kind: PieceKind::Rook,
+                    color,
+                })
+            && !self.castling_path_attacked(color, &[4, 5, 6])
+    }
+
+    fn can_castle_queenside(&self, color: Color) -> bool {
+        let (allowed, rank) = match color {
+            Color::White => (self.white_queenside, 0),
+            Color::Black => (self.black_queenside, 7),
+        };
+        // b-file must be empty too, but the king only passes d and c.
+        allowed
+            && self.get(Square(1, rank)).is_none()
+            && self.get(Square(2, rank)).is_none()
+            && self.get(Square(3, rank)).is_none()
+            && self.get(Square(0, rank))
+                == Some(Piece {
+                    kind: PieceKind::Rook,
+                    color,
+                })
+            && !self.castling_path_attacked(color, &[4, 3, 2])
+    }
 
-        let dx = (x2 - x1).signum();
-        let dy = (y2 - y1).signum();
+    /// The king may not castle out of, through, or into check.
+    fn castling_path_attacked(&self, color: Color, files: &[u8]) -> bool {
+        files.iter().any(|&file| {
+            self.is_square_attacked(Square(file, home_rank(color)), opposite_color(color))
+        })
+    }
 
-        let mut x = x1 + dx;
-        let mut y = y1 + dy;
+    /// Rights in the order white kingside, white queenside, black kingside,
+    /// black queenside.
+    pub fn castling_rights(&self) -> [bool; 4] {
+        [
+            self.white_kingside,
+            self.white_queenside,
+            self.black_kingside,
+            self.black_queenside,
+        ]
+    }
 
-        loop {
-            if x == x2 && y == y2 {
+    /// `moves` played from this position as SAN with move numbers, one
+    /// token each, e.g. ["12...", "Nc6", "13.", "Bb5"]. Stops at the first
+    /// move that is not legal.
+    pub fn numbered_san(&self, moves: &[Move]) -> Vec<String> {
+        let mut replay = self.clone();
+        let mut tokens = Vec::new();
+        for (i, &mv) in moves.iter().enumerate() {
+            if !replay.generate_legal_moves(replay.turn).contains(&mv) {
                 break;
             }
-
-            if self.get(Square(x as u8, y as u8)).is_some() {
-                return false;
+            if replay.turn == Color::White {
+                tokens.push(format!("{}.", replay.fullmove_number));
+            } else if i == 0 {
+                tokens.push(format!("{}...", replay.fullmove_number));
             }
-
-            x += dx;
-            y += dy;
+            tokens.push(replay.to_san(mv));
+            replay.make_move(mv);
         }
+        tokens
+    }
 
-        true
+    /// The played moves as numbered SAN, e.g. "1. e4 e5 2. Nf3".
+    #[allow(non_snake_case)]
+    pub fn moveHistoryString(&self) -> String {
+        let (start, moves) = self.rewind();
+        start.numbered_san(&moves).join(" ")
     }
 
-    fn find_king(&self, color: Color) -> Option<Square> {
-        for y in 0..8 {
-            for x in 0..8 {
-                if let Some(p) = self.squares[y][x] {
-                    if p.kind == PieceKind::King && p.color == color {
-                        return Some(Square(x as u8, y as u8));
-                    }
-                }
-            }
+    /// The position this board was set up with, and the moves played since.
+    pub fn rewind(&self) -> (Board, Vec<Move>) {
+        let mut start = self.clone();
+        let mut moves = Vec::new();
+        while let Some(mv) = start.unmake_move() {
+            moves.push(mv);
         }
-        None
+        moves.reverse();
+        (start, moves)
     }
 
-    fn would_cause_self_check(&self, mv: Move, color: Color) -> bool {
-        let mut cloned = self.clone();
-        cloned.apply_move(mv);
-        cloned.is_in_check(color)
+    /// The played moves in UCI notation, as sent after "position ... moves".
+    pub fn uci_moves(&self) -> String {
+        let moves: Vec<String> = self.played_moves().map(|mv| mv.to_string()).collect();
+        moves.join(" ")
     }
+}
 
-    fn can_castle_kingside(&self, color: Color) -> bool {
-        match color {
-            Color::White => {
-                if !self.white_kingside {
-                    return false;
-                }
-                if self.get(Square(5, 0)).is_some() {
-                    return false;
-                }
-                if self.get(Square(6, 0)).is_some() {
-                    return false;
-                }
-                if let Some(r) = self.get(Square(7, 0)) {
-                    if r.kind != PieceKind::Rook || r.color != Color::White {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-                true
-            }
-            Color::Black => {
-                if !self.black_kingside {
-                    return false;
-                }
-                if self.get(Square(5, 7)).is_some() {
-                    return false;
-                }
-                if self.get(Square(6, 7)).is_some() {
-                    return false;
-                }
-                if let Some(r) = self.get(Square(7, 7)) {
-                    if r.kind != PieceKind::Rook || r.color != Color::Black {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-                true
-            }
-        }
+const PROMOTION_KINDS: [Option<PieceKind>; 4] = [
+    Some(PieceKind::Queen),
+    Some(PieceKind::Rook),
+    Some(PieceKind::Bishop),
+    Some(PieceKind::Knight),
+];
+
+/// Where the rook starts and ends for a castling king move.
+fn castling_rook_squares(mv: Move) -> (Square, Square) {
+    let rank = mv.from.1;
+    if mv.to.0 > mv.from.0 {
+        (Square(7, rank), Square(5, rank))
+    } else {
+        (Square(0, rank), Square(3, rank))
     }
+}
 
-    fn can_castle_queenside(&self, color: Color) -> bool {
-        match color {
-            Color::White => {
-                if !self.white_queenside {
-                    return false;
-                }
-                // squares b1(1,0), c1(2,0), d1(3,0) must be empty (king passes through d1)
-                if self.get(Square(1, 0)).is_some() {
-                    return false;
-                }
-                if self.get(Square(2, 0)).is_some() {
-                    return false;
-                }
-                if self.get(Square(3, 0)).is_some() {
-                    return false;
-                }
-                if let Some(r) = self.get(Square(0, 0)) {
-                    if r.kind != PieceKind::Rook || r.color != Color::White {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-                true
-            }
-            Color::Black => {
-                if !self.black_queenside {
-                    return false;
-                }
-                if self.get(Square(1, 7)).is_some() {
-                    return false;
-                }
-                if self.get(Square(2, 7)).is_some() {
-                    return false;
-                }
-                if self.get(Square(3, 7)).is_some() {
-                    return false;
-                }
-                if let Some(r) = self.get(Square(0, 7)) {
-                    if r.kind != PieceKind::Rook || r.color != Color::Black {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-                true
-            }
-        }
+fn home_rank(color: Color) -> u8 {
+    match color {
+        Color::White => 0,
+        Color::Black => 7,
     }
+}
 
-    pub fn moveHistoryString(&self) -> String {
-        String::new()
+fn last_rank(color: Color) -> u8 {
+    match color {
+        Color::White => 7,
+        Color::Black => 0,
     }
 }
 
-fn opposite_color(c: Color) -> Color {
+pub(crate) fn opposite_color(c: Color) -> Color {
     match c {
         Color::White => Color::Black,
         Color::Black => Color::White,
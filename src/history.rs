@@ -0,0 +1,53 @@
+use crate::game::Board;
+use crate::types::Move;
+
+/// A board plus the moves that were taken back, so they can be replayed.
+pub struct GameHistory {
+    board: Board,
+    redo_stack: Vec<Move>,
+}
+
+impl GameHistory {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Plays a new move. A successful move discards anything left to redo.
+    pub fn play(&mut self, mv: Move) -> bool {
+        if !self.board.make_move(mv) {
+            return false;
+        }
+        self.redo_stack.clear();
+        true
+    }
+
+    pub fn undo(&mut self) -> Option<Move> {
+        let mv = self.board.unmake_move()?;
+        self.redo_stack.push(mv);
+        Some(mv)
+    }
+
+    pub fn redo(&mut self) -> Option<Move> {
+        let mv = self.redo_stack.pop()?;
+        if !self.board.make_move(mv) {
+            self.redo_stack.clear();
+            return None;
+        }
+        Some(mv)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.board.played_moves().next().is_some()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+}
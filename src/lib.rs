@@ -1,3 +1,17 @@
+pub mod bitboard;
+#[cfg(feature = "engine")]
+pub mod engine;
+pub mod fen;
 pub mod game;
+pub mod history;
+#[cfg(feature = "engine")]
+pub mod match_runner;
+pub mod perft;
+pub mod pgn;
+#[cfg(feature = "engine")]
+pub mod player;
+pub mod san;
+pub mod stats;
 pub mod types;
 pub mod ui;
+pub mod zobrist;
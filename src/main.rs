@@ -1,73 +1,450 @@
-mod engine;
-mod game;
-mod types;
-mod ui;
-
-use engine::Engine;
-use game::Board;
-use types::{Color, Move};
-use ui::UI;
+use chess_rust::engine::{Engine, EngineError, SearchInfo, SearchLimits};
+use chess_rust::game::{Board, DrawReason, Outcome};
+use chess_rust::history::GameHistory;
+use chess_rust::pgn::{self, GameResult, PgnReader, Tags};
+use chess_rust::player::{EnginePlayer, HumanPlayer, Player, RandomPlayer};
+use chess_rust::types::Color;
+use chess_rust::ui::{Command, UI};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, thread};
 
 fn main() {
+    // Usage: chess_rust [engine [engine args...]]
+    let mut engine_command: Vec<String> = env::args().skip(1).collect();
+    if engine_command.is_empty() {
+        engine_command.push("stockfish".to_string());
+    }
+
     println!("=== Terminal Chess ===");
     println!("1. Human vs Human");
-    println!("2. Human vs Stockfish");
+    println!("2. Human (White) vs Engine");
+    println!("3. Engine vs Human (Black)");
+    println!("4. Engine vs Engine");
+    println!("5. Human vs Random mover");
     println!("Choose mode: ");
 
     let mode = UI::read_line().trim().to_string();
-    let vs_engine = mode == "2";
-
-    let mut engine = if vs_engine { Some(Engine::new()) } else { None };
+    let human = || -> Box<dyn Player> { Box::new(HumanPlayer::new("Human")) };
+    let mut players: [Box<dyn Player>; 2] = match mode.as_str() {
+        "2" => [human(), engine_player(&engine_command)],
+        "3" => [engine_player(&engine_command), human()],
+        "4" => {
+            let white = read_engine_command("White", &engine_command);
+            let black = read_engine_command("Black", &engine_command);
+            [engine_player(&white), engine_player(&black)]
+        }
+        "5" => {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0);
+            [human(), Box::new(RandomPlayer::new("Random mover", seed))]
+        }
+        _ => [human(), human()],
+    };
+    start_game(&mut players);
+    // Analysis borrows a playing engine if there is one, or starts its own.
+    let mut analyst: Option<Engine> = None;
 
-    let mut board = Board::new();
-    let mut move_list = String::new();
+    let mut game = GameHistory::new(Board::new());
+    let tags = Tags {
+        event: "Terminal Chess".to_string(),
+        date: pgn::today(),
+        white: players[0].name().to_string(),
+        black: players[1].name().to_string(),
+        ..Tags::default()
+    };
 
-    loop {
-        UI::draw_board(&board);
+    let result = loop {
+        let board = game.board();
+        UI::draw_board(board);
 
-        if board.is_checkmate(board.turn) {
-            println!("Checkmate! {:?} loses.", board.turn);
-            println!("{:?} wins!", opposite(board.turn));
-            break;
+        let history = board.moveHistoryString();
+        if !history.is_empty() {
+            println!("Moves: {}\n", history);
         }
 
-        if board.is_stalemate(board.turn) {
-            println!("Stalemate! It's a draw.");
-            break;
+        let outcome = board.outcome();
+        match outcome {
+            Some(Outcome::Checkmate { winner }) => {
+                println!("Checkmate! {:?} loses.", opposite(winner));
+                println!("{:?} wins!", winner);
+                break GameResult::from_outcome(outcome);
+            }
+            Some(Outcome::Draw(reason)) => {
+                println!("{}! It's a draw.", draw_reason(reason));
+                break GameResult::from_outcome(outcome);
+            }
+            None => {}
         }
 
-        if board.is_in_check(board.turn) {
-            println!("{:?} is in check.", board.turn);
+        if board.is_in_check(board.turn()) {
+            println!("{:?} is in check.", board.turn());
         }
 
-        let mv = if vs_engine && board.turn == Color::Black {
-            let engine_move = engine
-                .as_mut()
-                .unwrap()
-                .bestMove(&move_list)
-                .expect("Stockfish did not return move");
-
-            let parsed = UI::parseMove(&engine_move).expect("Engine returned invalid move");
+        let player = &mut players[board.turn() as usize];
+        let claimable = board.claimable_draw();
+        if let Some(reason) = claimable {
+            // Only humans can type a claim, so other players always take it.
+            if !player.is_human() {
+                println!("{}! {} claims a draw.", draw_reason(reason), player.name());
+                break GameResult::Draw;
+            }
+            println!(
+                "{}: you may claim a draw by typing \"draw\", or play on.",
+                draw_reason(reason)
+            );
+        }
+        let command = match player.choose(board) {
+            Ok(command) => command,
+            Err(e) => {
+                println!("{} forfeits: {}.", player.name(), e);
+                break forfeit(board.turn());
+            }
+        };
 
-            println!("Stockfish plays: {}", engine_move);
-            parsed
-        } else {
-            match UI::readMove() {
-                Some(m) => m,
+        let mv = match command {
+            Command::Move(m) => {
+                if !player.is_human() {
+                    print!("{} plays: {}", player.name(), board.to_san(m));
+                    match player.comment() {
+                        Some(comment) => println!(" ({})", comment),
+                        None => println!(),
+                    }
+                }
+                m
+            }
+            // A take-back also covers the replies of non-human players, so
+            // a human is to move again afterwards.
+            Command::Undo => {
+                if !game.can_undo() {
+                    println!("Nothing to undo.\n");
+                }
+                game.undo();
+                while game.can_undo() && !players[game.board().turn() as usize].is_human() {
+                    game.undo();
+                }
+                continue;
+            }
+            Command::Redo => {
+                if !game.can_redo() {
+                    println!("Nothing to redo.\n");
+                }
+                game.redo();
+                while game.can_redo() && !players[game.board().turn() as usize].is_human() {
+                    game.redo();
+                }
+                continue;
+            }
+            Command::Save(path) => {
+                save_game(&game, &tags, GameResult::Unfinished, &path);
+                continue;
+            }
+            Command::Load(path) => {
+                if let Some(loaded) = load_game(&path) {
+                    game = loaded;
+                    start_game(&mut players);
+                }
+                continue;
+            }
+            Command::Analyze(lines) => {
+                let playing_engine = players.iter_mut().any(|p| p.engine().is_some());
+                if !playing_engine && analyst.is_none() {
+                    let (path, args) = split_command(&engine_command);
+                    match Engine::spawn(path, &args) {
+                        Ok(started) => analyst = Some(started),
+                        Err(e) => {
+                            println!("Could not start {}: {}.\n", path, e);
+                            continue;
+                        }
+                    }
+                }
+                let analyser = players
+                    .iter_mut()
+                    .find_map(|p| p.engine())
+                    .or(analyst.as_mut());
+                if let Some(analyser) = analyser
+                    && let Err(e) = analyze(analyser, game.board(), lines)
+                {
+                    println!("Analysis failed: {}.\n", e);
+                }
+                continue;
+            }
+            Command::ClaimDraw => match claimable {
+                Some(reason) => {
+                    println!("{}! Draw claimed.", draw_reason(reason));
+                    break GameResult::Draw;
+                }
                 None => {
-                    println!("Exiting game.");
-                    break;
+                    println!("There is no draw to claim.\n");
+                    continue;
                 }
+            },
+            Command::Quit => {
+                println!("Exiting game.");
+                break GameResult::Unfinished;
+            }
+            Command::Invalid(reason) => {
+                println!("Invalid input: {}.\n", reason);
+                continue;
             }
         };
 
-        if board.make_move(mv) {
-            move_list.push_str(&UI::move_to_string(mv));
-            move_list.push(' ');
-        } else {
+        if !game.play(mv) {
+            // Only a faulty non-human player gets here, and asking it
+            // again would get the same answer.
+            if !player.is_human() {
+                println!("{} forfeits: illegal move {}.", player.name(), mv);
+                break forfeit(game.board().turn());
+            }
             println!("Illegal move.\n");
+        }
+    };
+
+    if game.can_undo() {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        save_game(&game, &tags, result, &format!("game-{}.pgn", secs));
+    }
+}
+
+fn draw_reason(reason: DrawReason) -> &'static str {
+    match reason {
+        DrawReason::Stalemate => "Stalemate",
+        DrawReason::InsufficientMaterial => "Insufficient material",
+        DrawReason::ThreefoldRepetition => "Threefold repetition",
+        DrawReason::FivefoldRepetition => "Fivefold repetition",
+        DrawReason::FiftyMoveRule => "Fifty-move rule",
+        DrawReason::SeventyFiveMoveRule => "Seventy-five-move rule",
+    }
+}
+
+/// The result when `color` gives up the game.
+fn forfeit(color: Color) -> GameResult {
+    match color {
+        Color::White => GameResult::BlackWins,
+        Color::Black => GameResult::WhiteWins,
+    }
+}
+
+/// Starts the engine given as a command line and lets the user pick its
+/// difficulty. A human takes over the side if the engine cannot start.
+fn engine_player(command: &[String]) -> Box<dyn Player> {
+    let (path, args) = split_command(command);
+    match Engine::spawn(path, &args) {
+        Ok(mut engine) => {
+            let name = Path::new(path)
+                .file_stem()
+                .map_or(path.into(), |stem| stem.to_string_lossy());
+            println!("Difficulty for {}:", name);
+            choose_difficulty(&mut engine);
+            choose_pondering(&mut engine);
+            Box::new(EnginePlayer::new(&name, engine, engine_limits()))
+        }
+        Err(e) => {
+            println!("Could not start {}: {}.", path, e);
+            println!("A human plays this side instead.");
+            Box::new(HumanPlayer::new("Human"))
+        }
+    }
+}
+
+fn split_command(command: &[String]) -> (&str, Vec<&str>) {
+    let args = command.iter().skip(1).map(String::as_str).collect();
+    (&command[0], args)
+}
+
+/// Asks for an engine command line, e.g. "lc0 --threads=2"; an empty
+/// answer keeps the default.
+fn read_engine_command(side: &str, default: &[String]) -> Vec<String> {
+    println!("{} engine [{}]: ", side, default.join(" "));
+    let words: Vec<String> = UI::read_line()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if words.is_empty() {
+        default.to_vec()
+    } else {
+        words
+    }
+}
+
+/// Tells the players a game is starting. A player that cannot start is
+/// replaced by a human.
+fn start_game(players: &mut [Box<dyn Player>; 2]) {
+    for player in players.iter_mut() {
+        if let Err(e) = player.new_game() {
+            println!("{} failed: {}.", player.name(), e);
+            println!("A human plays this side instead.");
+            *player = Box::new(HumanPlayer::new("Human"));
+        }
+    }
+}
+
+/// Analyses until the user presses Enter, printing the lines each time the
+/// engine finishes a depth.
+fn analyze(engine: &mut Engine, board: &Board, lines: u32) -> Result<(), EngineError> {
+    println!("Analysing, press Enter to stop.");
+    let (tx, enter) = mpsc::channel();
+    thread::spawn(move || {
+        UI::read_line();
+        let _ = tx.send(());
+    });
+
+    let limits = SearchLimits {
+        infinite: true,
+        ..SearchLimits::default()
+    };
+    let expected = board
+        .generate_legal_moves(board.turn())
+        .len()
+        .min(lines as usize);
+    let mut analysis = engine.analyze(board, lines, &limits)?;
+    let mut shown = 0;
+    while enter.try_recv() == Err(TryRecvError::Empty) && !analysis.is_finished() {
+        if !analysis.wait(Duration::from_millis(100))? {
             continue;
         }
+        // Lines arrive one by one for each depth; show them once all of
+        // them have caught up.
+        let depths: Vec<u32> = analysis.lines().values().filter_map(|i| i.depth).collect();
+        if let Some(&depth) = depths.first()
+            && depth > shown
+            && depths.len() >= expected
+            && depths.iter().all(|&d| d == depth)
+        {
+            shown = depth;
+            print_lines(board, depth, analysis.lines());
+        }
+    }
+    analysis.stop()?;
+    println!();
+    Ok(())
+}
+
+/// Scores are shown from White's point of view.
+fn print_lines(board: &Board, depth: u32, lines: &BTreeMap<u32, SearchInfo>) {
+    println!("Depth {}:", depth);
+    for (index, info) in lines {
+        let score = match info.score {
+            Some(score) if board.turn() == Color::Black => (-score).to_string(),
+            Some(score) => score.to_string(),
+            None => "?".to_string(),
+        };
+        println!(
+            "{:>2}. {:>7}  {}",
+            index,
+            score,
+            UI::line_to_san(board, &info.pv)
+        );
+    }
+}
+
+/// Difficulty levels as a target Elo and, for engines without an Elo limit,
+/// a "Skill Level"; `None` is full strength.
+const DIFFICULTIES: [(&str, Option<(i64, i64)>); 5] = [
+    ("Beginner", Some((1350, 0))),
+    ("Casual", Some((1600, 5))),
+    ("Club", Some((1900, 10))),
+    ("Expert", Some((2300, 15))),
+    ("Full strength", None),
+];
+
+fn choose_difficulty(engine: &mut Engine) {
+    for (i, (name, _)) in DIFFICULTIES.iter().enumerate() {
+        println!("{}. {}", i + 1, name);
+    }
+    println!("Choose difficulty: ");
+
+    let level = UI::read_line()
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|n| (1..=DIFFICULTIES.len()).contains(n))
+        .unwrap_or(DIFFICULTIES.len());
+    if let Err(e) = set_difficulty(engine, DIFFICULTIES[level - 1].1) {
+        println!("Could not set difficulty: {}.", e);
+    }
+}
+
+/// Prefers "UCI_LimitStrength" with "UCI_Elo" and falls back to "Skill
+/// Level", clamping the value to whatever range the engine advertises.
+fn set_difficulty(engine: &mut Engine, strength: Option<(i64, i64)>) -> Result<(), EngineError> {
+    let Some((elo, skill)) = strength else {
+        return Ok(());
+    };
+    let clamp =
+        |value: i64, name: &str| engine.options().get(name).map(|option| option.clamp(value));
+
+    let limit_strength = engine.options().get("UCI_LimitStrength").is_some();
+    if let Some(elo) = clamp(elo, "UCI_Elo")
+        && limit_strength
+    {
+        engine.set_option("UCI_LimitStrength", "true")?;
+        engine.set_option("UCI_Elo", &elo.to_string())?;
+    } else if let Some(skill) = clamp(skill, "Skill Level") {
+        engine.set_option("Skill Level", &skill.to_string())?;
+    } else {
+        println!("The engine has no strength settings; it plays at full strength.");
+    }
+    engine.wait_ready()
+}
+
+/// Offers to let the engine think on its opponent's time, if it can.
+fn choose_pondering(engine: &mut Engine) {
+    if engine.options().get("Ponder").is_none() {
+        return;
+    }
+    println!("Let it think on the opponent's time? (y/N): ");
+    if !UI::read_line().trim().eq_ignore_ascii_case("y") {
+        return;
+    }
+    if let Err(e) = engine.set_option("Ponder", "true") {
+        println!("Could not turn on pondering: {}.", e);
+    }
+}
+
+/// How long the engine may think. The game has no clock, so this is a
+/// fixed depth; with one, pass its remaining times and increments.
+fn engine_limits() -> SearchLimits {
+    SearchLimits::depth(12)
+}
+
+/// Replaces the current game with the first game in a PGN file.
+fn load_game(path: &str) -> Option<GameHistory> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("Could not read {}: {}.\n", path, e);
+            return None;
+        }
+    };
+    match PgnReader::new(&text).next() {
+        Some(Ok(loaded)) => {
+            println!("Loaded {}.\n", path);
+            Some(GameHistory::new(loaded.board))
+        }
+        Some(Err(e)) => {
+            println!("Could not load {}: {}.\n", path, e);
+            None
+        }
+        None => {
+            println!("No game found in {}.\n", path);
+            None
+        }
+    }
+}
+
+fn save_game(game: &GameHistory, tags: &Tags, result: GameResult, path: &str) {
+    let text = pgn::write_pgn(game.board(), tags, result);
+    match fs::write(path, text) {
+        Ok(()) => println!("Game saved to {}.\n", path),
+        Err(e) => println!("Could not save {}: {}.\n", path, e),
     }
 }
 
@@ -0,0 +1,303 @@
+use crate::engine::{Engine, EngineError, Score, SearchLimits};
+use crate::game::{Board, Outcome};
+use crate::pgn::GameResult;
+use crate::player::{EnginePlayer, Player};
+use crate::stats::{MatchScore, Sprt, SprtVerdict};
+use crate::types::Color;
+use crate::ui::Command;
+
+/// Centipawn scores at least this large are treated as won, which is how
+/// engines report tablebase wins short of an actual mate.
+const WON_SCORE: i32 = 10_000;
+
+/// An engine and the options it plays with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineConfig {
+    pub name: String,
+    /// The executable followed by its arguments.
+    pub command: Vec<String>,
+    /// Sent with "setoption" before the first game.
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineConfig {
+    pub fn start(&self, limits: &SearchLimits) -> Result<EnginePlayer, EngineError> {
+        let (path, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| EngineError::NotFound(String::new()))?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let mut engine = Engine::spawn(path, &args)?;
+        for (name, value) in &self.options {
+            engine.set_option(name, value)?;
+        }
+        engine.wait_ready()?;
+        Ok(EnginePlayer::new(&self.name, engine, limits.clone()))
+    }
+}
+
+/// When to end a game early on the engines' say-so. Scores are in
+/// centipawns; a rule without a score is off.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Adjudication {
+    /// A side loses once both engines have scored the game at least this
+    /// far in the other side's favour for `resign_moves` moves each.
+    pub resign_score: Option<i32>,
+    pub resign_moves: usize,
+    /// A draw once both engines have scored the game within this much of
+    /// equal for `draw_moves` moves each, from move `draw_after` on.
+    pub draw_score: Option<i32>,
+    pub draw_moves: usize,
+    pub draw_after: u32,
+    /// With at most this many pieces left, trust the score of an engine
+    /// that reports tablebase hits.
+    pub tablebase_pieces: Option<u32>,
+    /// A draw after this many moves by each side.
+    pub max_moves: Option<u32>,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            resign_score: None,
+            resign_moves: 3,
+            draw_score: None,
+            draw_moves: 8,
+            draw_after: 40,
+            tablebase_pieces: None,
+            max_moves: None,
+        }
+    }
+}
+
+/// Why a game ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    /// Checkmate or one of the draw rules.
+    Rules,
+    Resignation,
+    DrawAdjudication,
+    Tablebase,
+    MoveLimit,
+    /// A player failed to produce a legal move and lost.
+    Forfeit(String),
+}
+
+impl Termination {
+    /// The value of the PGN "Termination" tag.
+    pub fn pgn_tag(&self) -> &'static str {
+        match self {
+            Termination::Rules => "normal",
+            Termination::Forfeit(_) => "rules infraction",
+            _ => "adjudication",
+        }
+    }
+}
+
+pub struct GameRecord {
+    /// The final position, with every move played from the opening on.
+    pub board: Board,
+    pub result: GameResult,
+    pub termination: Termination,
+}
+
+/// Plays one game from `start`, which may already have moves played.
+pub fn play_game(
+    start: &Board,
+    white: &mut dyn Player,
+    black: &mut dyn Player,
+    adjudication: &Adjudication,
+) -> GameRecord {
+    let mut board = start.clone();
+    let finish = |board: Board, result, termination| GameRecord {
+        board,
+        result,
+        termination,
+    };
+
+    for color in [Color::White, Color::Black] {
+        let player: &mut dyn Player = match color {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+        if let Err(e) = player.new_game() {
+            let reason = format!("{} failed: {}", player.name(), e);
+            return finish(board, loss_for(color), Termination::Forfeit(reason));
+        }
+    }
+
+    // White's view of the game after each move, as far as the mover knew.
+    let mut scores: Vec<Option<i32>> = Vec::new();
+    let mut plies = 0;
+    loop {
+        // Engines cannot claim draws, so the runner claims them for both.
+        let outcome = board
+            .outcome()
+            .or(board.claimable_draw().map(Outcome::Draw));
+        if outcome.is_some() {
+            return finish(board, GameResult::from_outcome(outcome), Termination::Rules);
+        }
+        if adjudication.max_moves.is_some_and(|max| plies >= max * 2) {
+            return finish(board, GameResult::Draw, Termination::MoveLimit);
+        }
+
+        let mover = board.turn();
+        let player: &mut dyn Player = match mover {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+        let mv = match player.choose(&board) {
+            Ok(Command::Move(mv)) => mv,
+            Ok(_) => {
+                let reason = format!("{} did not play a move", player.name());
+                return finish(board, loss_for(mover), Termination::Forfeit(reason));
+            }
+            Err(e) => {
+                let reason = format!("{} failed: {}", player.name(), e);
+                return finish(board, loss_for(mover), Termination::Forfeit(reason));
+            }
+        };
+        if !board.make_move(mv) {
+            let reason = format!("{} played the illegal move {}", player.name(), mv);
+            return finish(board, loss_for(mover), Termination::Forfeit(reason));
+        }
+        plies += 1;
+
+        let info = player.last_info();
+        let score = info.and_then(|i| i.score).map(|s| match mover {
+            Color::White => centipawns(s),
+            Color::Black => -centipawns(s),
+        });
+        scores.push(score);
+
+        let tablebase = adjudication.tablebase_pieces.is_some_and(|pieces| {
+            board.occupied().count_ones() <= pieces && info.and_then(|i| i.tbhits) > Some(0)
+        });
+        if tablebase && let Some(score) = score {
+            let result = match score {
+                0 => Some(GameResult::Draw),
+                s if s >= WON_SCORE => Some(GameResult::WhiteWins),
+                s if s <= -WON_SCORE => Some(GameResult::BlackWins),
+                _ => None,
+            };
+            if let Some(result) = result {
+                return finish(board, result, Termination::Tablebase);
+            }
+        }
+
+        if let Some(threshold) = adjudication.resign_score {
+            let recent = recent_scores(&scores, adjudication.resign_moves);
+            if recent
+                .as_ref()
+                .is_some_and(|r| r.iter().all(|&s| s >= threshold))
+            {
+                return finish(board, GameResult::WhiteWins, Termination::Resignation);
+            }
+            if recent.is_some_and(|r| r.iter().all(|&s| s <= -threshold)) {
+                return finish(board, GameResult::BlackWins, Termination::Resignation);
+            }
+        }
+
+        if let Some(threshold) = adjudication.draw_score
+            && board.fullmove_number >= adjudication.draw_after
+            && recent_scores(&scores, adjudication.draw_moves)
+                .is_some_and(|r| r.iter().all(|&s| s.abs() <= threshold))
+        {
+            return finish(board, GameResult::Draw, Termination::DrawAdjudication);
+        }
+    }
+}
+
+/// The scores of the last `moves` moves by each side, if all of them had
+/// one.
+fn recent_scores(scores: &[Option<i32>], moves: usize) -> Option<Vec<i32>> {
+    let plies = moves * 2;
+    if moves == 0 || scores.len() < plies {
+        return None;
+    }
+    scores[scores.len() - plies..].iter().copied().collect()
+}
+
+/// Mate scores become larger than any centipawn score, and shorter mates
+/// larger than longer ones.
+fn centipawns(score: Score) -> i32 {
+    match score {
+        Score::Cp(cp) => cp,
+        Score::Mate(n) if n > 0 => 2 * WON_SCORE - n,
+        Score::Mate(n) => -2 * WON_SCORE - n,
+    }
+}
+
+fn loss_for(color: Color) -> GameResult {
+    match color {
+        Color::White => GameResult::BlackWins,
+        Color::Black => GameResult::WhiteWins,
+    }
+}
+
+pub struct MatchConfig {
+    pub games: u32,
+    /// Each opening is played twice, once with each player as White. With
+    /// none, every game starts from the standard position.
+    pub openings: Vec<Board>,
+    pub adjudication: Adjudication,
+    /// Stop early once the test reaches a verdict.
+    pub sprt: Option<Sprt>,
+}
+
+/// One finished game of a match.
+pub struct MatchGame {
+    /// Counting from 1.
+    pub round: u32,
+    pub first_is_white: bool,
+    pub record: GameRecord,
+}
+
+/// Plays a match, alternating colours, and returns the score from the
+/// first player's point of view. `on_game` sees each game as it finishes,
+/// along with the score so far.
+pub fn run_match(
+    first: &mut dyn Player,
+    second: &mut dyn Player,
+    config: &MatchConfig,
+    mut on_game: impl FnMut(&MatchGame, &MatchScore),
+) -> MatchScore {
+    let mut score = MatchScore::default();
+    for index in 0..config.games {
+        let start = match config.openings.len() {
+            0 => Board::new(),
+            n => config.openings[(index as usize / 2) % n].clone(),
+        };
+        let first_is_white = index % 2 == 0;
+        let record = if first_is_white {
+            play_game(&start, first, second, &config.adjudication)
+        } else {
+            play_game(&start, second, first, &config.adjudication)
+        };
+
+        let first_wins = match record.result {
+            GameResult::WhiteWins => Some(first_is_white),
+            GameResult::BlackWins => Some(!first_is_white),
+            GameResult::Draw | GameResult::Unfinished => None,
+        };
+        match first_wins {
+            Some(true) => score.wins += 1,
+            Some(false) => score.losses += 1,
+            None => score.draws += 1,
+        }
+
+        let game = MatchGame {
+            round: index + 1,
+            first_is_white,
+            record,
+        };
+        on_game(&game, &score);
+        if config
+            .sprt
+            .is_some_and(|sprt| sprt.verdict(&score) != SprtVerdict::Continue)
+        {
+            break;
+        }
+    }
+    score
+}
@@ -0,0 +1,44 @@
+use crate::game::Board;
+use crate::types::Move;
+
+impl Board {
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep.
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().perft_nodes(depth)
+    }
+
+    /// Perft split by root move, in generation order.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut board = self.clone();
+        board
+            .generate_legal_moves(board.turn())
+            .into_iter()
+            .map(|mv| {
+                let undo = board.apply_move(mv);
+                let nodes = board.perft_nodes(depth - 1);
+                board.revert_move(&undo);
+                (mv, nodes)
+            })
+            .collect()
+    }
+
+    fn perft_nodes(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_legal_moves(self.turn());
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            let undo = self.apply_move(mv);
+            nodes += self.perft_nodes(depth - 1);
+            self.revert_move(&undo);
+        }
+        nodes
+    }
+}
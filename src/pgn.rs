@@ -0,0 +1,560 @@
+use crate::fen::{FenError, STARTING_FEN};
+use crate::game::{Board, Outcome};
+use crate::san::SanError;
+use crate::types::Color;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Export-format lines stay below 80 columns.
+const LINE_WIDTH: usize = 79;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unfinished,
+}
+
+impl GameResult {
+    pub fn from_outcome(outcome: Option<Outcome>) -> Self {
+        match outcome {
+            Some(Outcome::Checkmate {
+                winner: Color::White,
+            }) => GameResult::WhiteWins,
+            Some(Outcome::Checkmate {
+                winner: Color::Black,
+            }) => GameResult::BlackWins,
+            Some(Outcome::Draw(_)) => GameResult::Draw,
+            None => GameResult::Unfinished,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+}
+
+/// The Seven Tag Roster minus Result, which comes from the game itself.
+#[derive(Clone, Debug)]
+pub struct Tags {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+    /// Written after the roster, in order.
+    pub extra: Vec<(String, String)>,
+}
+
+impl Default for Tags {
+    fn default() -> Self {
+        Self {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+            extra: Vec::new(),
+        }
+    }
+}
+
+/// Renders every move played on `board` as a PGN game. A SetUp/FEN pair is
+/// added when the game did not start from the standard position.
+pub fn write_pgn(board: &Board, tags: &Tags, result: GameResult) -> String {
+    let (start, moves) = board.rewind();
+
+    let mut pgn = String::new();
+    let roster = [
+        ("Event", &tags.event),
+        ("Site", &tags.site),
+        ("Date", &tags.date),
+        ("Round", &tags.round),
+        ("White", &tags.white),
+        ("Black", &tags.black),
+    ];
+    for (name, value) in roster {
+        push_tag(&mut pgn, name, value);
+    }
+    push_tag(&mut pgn, "Result", result.as_str());
+
+    let start_fen = start.to_fen();
+    if start_fen != STARTING_FEN {
+        push_tag(&mut pgn, "SetUp", "1");
+        push_tag(&mut pgn, "FEN", &start_fen);
+    }
+    for (name, value) in &tags.extra {
+        push_tag(&mut pgn, name, value);
+    }
+    pgn.push('\n');
+
+    let mut tokens = start.numbered_san(&moves);
+    tokens.push(result.as_str().to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push_str("\n\n");
+    pgn
+}
+
+/// Today's date in the "YYYY.MM.DD" form of the Date tag (UTC).
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}.{:02}.{:02}", y, m, d)
+}
+
+fn push_tag(pgn: &mut String, name: &str, value: &str) {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    pgn.push_str(&format!("[{} \"{}\"]\n", name, escaped));
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[derive(Clone)]
+pub struct PgnGame {
+    /// Every tag pair in file order, the Seven Tag Roster included.
+    pub tags: Vec<(String, String)>,
+    /// The final position; its move list rewinds to the starting one.
+    pub board: Board,
+    pub result: GameResult,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgnError {
+    /// 1-based index of the game within the file.
+    pub game: usize,
+    /// 1-based ply of the offending move, or 0 for problems outside the moves.
+    pub ply: usize,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    MalformedTag(String),
+    UnterminatedComment,
+    UnbalancedVariation,
+    Fen(FenError),
+    Move { san: String, error: SanError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "game {}", self.game)?;
+        if self.ply > 0 {
+            write!(f, ", ply {}", self.ply)?;
+        }
+        match &self.kind {
+            PgnErrorKind::MalformedTag(t) => write!(f, ": malformed tag pair '{}'", t),
+            PgnErrorKind::UnterminatedComment => write!(f, ": unterminated comment"),
+            PgnErrorKind::UnbalancedVariation => write!(f, ": unbalanced variation"),
+            PgnErrorKind::Fen(e) => write!(f, ": bad FEN tag: {}", e),
+            PgnErrorKind::Move { error, .. } => write!(f, ": {}", error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// Parses every game in `text`, stopping at the first error.
+pub fn read_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    PgnReader::new(text).collect()
+}
+
+/// Yields the games of a PGN file one by one. After an error the reader
+/// skips to the next game, so batch jobs can carry on past a bad one.
+pub struct PgnReader<'a> {
+    lexer: Lexer<'a>,
+    games_read: usize,
+}
+
+impl<'a> PgnReader<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            lexer: Lexer::new(text),
+            games_read: 0,
+        }
+    }
+
+    fn read_game(&mut self) -> Option<Result<PgnGame, PgnError>> {
+        let index = self.games_read + 1;
+        let mut tags = Vec::new();
+        let mut board: Option<Board> = None;
+        let mut ply = 0;
+        let mut depth = 0usize;
+        // Anything but a tag, so that tags after it start a new game.
+        let mut movetext = false;
+
+        let fail = |ply, kind| PgnError {
+            game: index,
+            ply,
+            kind,
+        };
+
+        loop {
+            let token = match self.lexer.next_token() {
+                Ok(Some(token)) => token,
+                Ok(None) => break,
+                Err(kind) => return Some(Err(fail(ply, kind))),
+            };
+
+            if !matches!(token, Token::Tag(..)) {
+                movetext = true;
+            }
+            match token {
+                Token::Tag(name, value) => {
+                    if movetext || (!tags.is_empty() && self.lexer.blank_line) {
+                        // A new tag section without a result token: the
+                        // previous game ended early.
+                        self.lexer.unread(Token::Tag(name, value));
+                        break;
+                    }
+                    tags.push((name, value));
+                }
+                Token::Comment | Token::Nag => {}
+                Token::VariationStart => depth += 1,
+                Token::VariationEnd => {
+                    if depth == 0 {
+                        return Some(Err(fail(ply, PgnErrorKind::UnbalancedVariation)));
+                    }
+                    depth -= 1;
+                }
+                Token::Result(result) if depth == 0 => {
+                    let board = match board {
+                        Some(b) => b,
+                        None => match start_board(&tags) {
+                            Ok(b) => b,
+                            Err(kind) => return Some(Err(fail(0, kind))),
+                        },
+                    };
+                    self.games_read += 1;
+                    return Some(Ok(PgnGame {
+                        tags,
+                        board,
+                        result,
+                    }));
+                }
+                Token::Result(_) => {}
+                Token::Symbol(_) if depth > 0 => {}
+                Token::Symbol(symbol) => {
+                    let board = match &mut board {
+                        Some(b) => b,
+                        None => match start_board(&tags) {
+                            Ok(b) => board.insert(b),
+                            Err(kind) => return Some(Err(fail(0, kind))),
+                        },
+                    };
+
+                    let san = strip_move_number(&symbol);
+                    if san.is_empty() {
+                        continue;
+                    }
+                    ply += 1;
+                    match board.parse_san(san) {
+                        Ok(mv) => {
+                            board.make_move(mv);
+                        }
+                        Err(error) => {
+                            let kind = PgnErrorKind::Move {
+                                san: san.to_string(),
+                                error,
+                            };
+                            return Some(Err(fail(ply, kind)));
+                        }
+                    }
+                }
+            }
+        }
+
+        if depth > 0 {
+            return Some(Err(fail(ply, PgnErrorKind::UnbalancedVariation)));
+        }
+        if tags.is_empty() && board.is_none() {
+            return None;
+        }
+
+        let board = match board {
+            Some(b) => b,
+            None => match start_board(&tags) {
+                Ok(b) => b,
+                Err(kind) => return Some(Err(fail(0, kind))),
+            },
+        };
+        let result = tags
+            .iter()
+            .find(|(n, _)| n == "Result")
+            .and_then(|(_, v)| parse_result(v))
+            .unwrap_or(GameResult::Unfinished);
+        self.games_read += 1;
+        Some(Ok(PgnGame {
+            tags,
+            board,
+            result,
+        }))
+    }
+
+    /// Discards the rest of a game that failed to parse.
+    fn skip_game(&mut self) {
+        let mut in_moves = false;
+        loop {
+            match self.lexer.next_token() {
+                Ok(None) => return,
+                Ok(Some(Token::Result(_))) => return,
+                Ok(Some(Token::Tag(name, value))) if in_moves => {
+                    self.lexer.unread(Token::Tag(name, value));
+                    return;
+                }
+                Ok(Some(Token::Symbol(_))) => in_moves = true,
+                Ok(Some(_)) => {}
+                // An unterminated comment runs to the end of the input.
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+impl Iterator for PgnReader<'_> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.read_game()?;
+        if item.is_err() {
+            self.games_read += 1;
+            self.skip_game();
+        }
+        Some(item)
+    }
+}
+
+fn start_board(tags: &[(String, String)]) -> Result<Board, PgnErrorKind> {
+    match tags.iter().find(|(n, _)| n == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen).map_err(PgnErrorKind::Fen),
+        None => Ok(Board::new()),
+    }
+}
+
+fn parse_result(text: &str) -> Option<GameResult> {
+    match text {
+        "1-0" => Some(GameResult::WhiteWins),
+        "0-1" => Some(GameResult::BlackWins),
+        "1/2-1/2" => Some(GameResult::Draw),
+        "*" => Some(GameResult::Unfinished),
+        _ => None,
+    }
+}
+
+/// "12.", "12..." and "12.e4" all carry a move number in front.
+fn strip_move_number(symbol: &str) -> &str {
+    let rest = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == symbol.len() || !rest.starts_with('.') {
+        // Not a move number: "e4", or castling written with zeros.
+        return symbol;
+    }
+    rest.trim_start_matches('.')
+}
+
+enum Token {
+    Tag(String, String),
+    Comment,
+    Nag,
+    VariationStart,
+    VariationEnd,
+    Result(GameResult),
+    Symbol(String),
+}
+
+struct Lexer<'a> {
+    text: &'a str,
+    pos: usize,
+    pending: Option<Token>,
+    /// Whether a blank line came before the last token read.
+    blank_line: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            pos: 0,
+            pending: None,
+            blank_line: false,
+        }
+    }
+
+    fn unread(&mut self, token: Token) {
+        self.pending = Some(token);
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnErrorKind> {
+        if let Some(token) = self.pending.take() {
+            return Ok(Some(token));
+        }
+
+        self.blank_line = false;
+        loop {
+            let trimmed = self.rest().trim_start();
+            let skipped = &self.rest()[..self.rest().len() - trimmed.len()];
+            self.blank_line |= skipped.matches('\n').count() > 1;
+            self.pos = self.text.len() - trimmed.len();
+
+            let at_line_start = self.text[..self.pos]
+                .chars()
+                .next_back()
+                .is_none_or(|c| c == '\n');
+            match trimmed.chars().next() {
+                None => return Ok(None),
+                // Escape lines and ';' comments run to the end of the line.
+                Some('%') if at_line_start => self.skip_line(),
+                Some(';') => self.skip_line(),
+                Some(_) => break,
+            }
+        }
+
+        let rest = self.rest();
+        let c = rest.chars().next().unwrap_or(' ');
+        match c {
+            '{' => match rest.find('}') {
+                Some(end) => {
+                    self.pos += end + 1;
+                    Ok(Some(Token::Comment))
+                }
+                None => {
+                    self.pos = self.text.len();
+                    Err(PgnErrorKind::UnterminatedComment)
+                }
+            },
+            '(' => {
+                self.pos += 1;
+                Ok(Some(Token::VariationStart))
+            }
+            ')' => {
+                self.pos += 1;
+                Ok(Some(Token::VariationEnd))
+            }
+            '[' => self.tag().map(Some),
+            '$' => {
+                let digits = rest[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - 1);
+                self.pos += 1 + digits;
+                Ok(Some(Token::Nag))
+            }
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || "{}()[];$".contains(c))
+                    .unwrap_or(rest.len())
+                    .max(c.len_utf8());
+                let symbol = &rest[..len];
+                self.pos += len;
+                Ok(Some(match parse_result(symbol) {
+                    Some(result) => Token::Result(result),
+                    None => Token::Symbol(symbol.to_string()),
+                }))
+            }
+        }
+    }
+
+    fn skip_line(&mut self) {
+        match self.rest().find('\n') {
+            Some(end) => self.pos += end + 1,
+            None => self.pos = self.text.len(),
+        }
+    }
+
+    /// `[Name "value"]`, with `\"` and `\\` escapes inside the value.
+    fn tag(&mut self) -> Result<Token, PgnErrorKind> {
+        let rest = self.rest();
+        let malformed = |end: usize| PgnErrorKind::MalformedTag(rest[..end].to_string());
+
+        let line_end = rest.find('\n').unwrap_or(rest.len());
+        let body = rest[1..line_end].trim_start();
+        let name_len = body
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(body.len());
+        let name = &body[..name_len];
+        let after_name = body[name_len..].trim_start();
+
+        if name.is_empty() || !after_name.starts_with('"') {
+            self.skip_line();
+            return Err(malformed(line_end));
+        }
+
+        let mut value = String::new();
+        let mut chars = after_name[1..].char_indices();
+        let mut closed_at = None;
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    if let Some((_, escaped)) = chars.next() {
+                        value.push(escaped);
+                    }
+                }
+                '"' => {
+                    closed_at = Some(i);
+                    break;
+                }
+                _ => value.push(c),
+            }
+        }
+
+        let tail = match closed_at {
+            Some(i) => after_name[1 + i + 1..].trim_start(),
+            None => "",
+        };
+        if closed_at.is_none() || !tail.starts_with(']') {
+            self.skip_line();
+            return Err(malformed(line_end));
+        }
+
+        let consumed = line_end - tail.len() + 1;
+        self.pos += consumed;
+        Ok(Token::Tag(name.to_string(), value))
+    }
+}
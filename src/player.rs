@@ -0,0 +1,280 @@
+use crate::engine::{Engine, EngineError, SearchInfo, SearchLimits, SearchResult};
+use crate::game::Board;
+use crate::ui::{Command, UI};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// How many times an engine that crashes while thinking is restarted
+/// before it gives up the move.
+pub const MAX_RESTARTS: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlayerError {
+    Engine(EngineError),
+    /// The engine kept crashing, even after this many restarts.
+    Crashed {
+        restarts: u32,
+        error: EngineError,
+    },
+    /// The player had no move to offer, e.g. a script that ran out or an
+    /// engine that answered "bestmove (none)".
+    NoMove,
+    /// The player answered with a move that is not legal here, e.g. an
+    /// engine's bestmove or a script entry.
+    IllegalMove(String),
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerError::Engine(e) => write!(f, "{}", e),
+            PlayerError::Crashed { restarts, error } => {
+                write!(f, "{}, even after {} restarts", error, restarts)
+            }
+            PlayerError::NoMove => write!(f, "no move to play"),
+            PlayerError::IllegalMove(mv) => write!(f, "illegal move '{}'", mv),
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+impl From<EngineError> for PlayerError {
+    fn from(e: EngineError) -> Self {
+        PlayerError::Engine(e)
+    }
+}
+
+/// Something that picks moves for one side.
+pub trait Player {
+    fn name(&self) -> &str;
+
+    /// What to do in this position, with this player's side to move. Only
+    /// humans answer with anything but [`Command::Move`].
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError>;
+
+    /// Take-backs skip over positions where a non-human is to move.
+    fn is_human(&self) -> bool {
+        false
+    }
+
+    /// Called before a game, and whenever a different game is loaded.
+    fn new_game(&mut self) -> Result<(), PlayerError> {
+        Ok(())
+    }
+
+    /// A note on the last move chosen, such as an engine's evaluation.
+    fn comment(&self) -> Option<String> {
+        None
+    }
+
+    /// What the search behind the last move chosen found, for players that
+    /// search.
+    fn last_info(&self) -> Option<&SearchInfo> {
+        None
+    }
+
+    /// The engine behind this player, if any, e.g. to borrow for analysis.
+    fn engine(&mut self) -> Option<&mut Engine> {
+        None
+    }
+}
+
+/// Reads moves and commands from the terminal.
+pub struct HumanPlayer {
+    name: String,
+}
+
+impl HumanPlayer {
+    pub fn new(name: &str) -> Self {
+        HumanPlayer {
+            name: name.to_string(),
+        }
+    }
+}
+
+impl Player for HumanPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError> {
+        Ok(UI::read_command(board))
+    }
+
+    fn is_human(&self) -> bool {
+        true
+    }
+}
+
+/// A UCI engine searching with fixed limits. With the engine's "Ponder"
+/// option on, it keeps thinking on the opponent's time, assuming they play
+/// the move it expects. An engine that crashes is restarted up to
+/// [`MAX_RESTARTS`] times per move.
+pub struct EnginePlayer {
+    name: String,
+    engine: Engine,
+    limits: SearchLimits,
+    last: Option<SearchResult>,
+}
+
+impl EnginePlayer {
+    pub fn new(name: &str, engine: Engine, limits: SearchLimits) -> Self {
+        EnginePlayer {
+            name: name.to_string(),
+            engine,
+            limits,
+            last: None,
+        }
+    }
+}
+
+impl Player for EnginePlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError> {
+        self.last = None;
+        let mut result = self.engine.search(board, &self.limits, |_| {});
+        let mut restarts = 0;
+        while let Err(e) = &result
+            && e.is_crash()
+        {
+            if restarts == MAX_RESTARTS {
+                let error = e.clone();
+                return Err(PlayerError::Crashed { restarts, error });
+            }
+            restarts += 1;
+            // The search sends the position again.
+            result = self
+                .engine
+                .restart()
+                .and_then(|()| self.engine.search(board, &self.limits, |_| {}));
+        }
+        let result = result?;
+
+        let best = match result.best.as_deref() {
+            None | Some("(none)" | "0000") => return Err(PlayerError::NoMove),
+            Some(best) => best.to_string(),
+        };
+        let legal = board.generate_legal_moves(board.turn());
+        let mv = UI::parseMove(&best)
+            .filter(|mv| legal.contains(mv))
+            .ok_or(PlayerError::IllegalMove(best))?;
+        let predicted = result.ponder.as_deref().and_then(UI::parseMove);
+        self.last = Some(result);
+
+        if self.engine.ponder_enabled()
+            && let Some(predicted) = predicted
+        {
+            let mut after = board.clone();
+            if after.make_move(mv)
+                && after
+                    .generate_legal_moves(after.turn())
+                    .contains(&predicted)
+            {
+                // A failure shows up, and is recovered from, in the next
+                // search.
+                let _ = self.engine.ponder(&after, predicted, &self.limits);
+            }
+        }
+        Ok(Command::Move(mv))
+    }
+
+    fn new_game(&mut self) -> Result<(), PlayerError> {
+        self.last = None;
+        Ok(self.engine.new_game()?)
+    }
+
+    fn comment(&self) -> Option<String> {
+        let info = self.last_info()?;
+        Some(format!("depth {}, score {}", info.depth?, info.score?))
+    }
+
+    fn last_info(&self) -> Option<&SearchInfo> {
+        self.last.as_ref().map(|result| &result.info)
+    }
+
+    fn engine(&mut self) -> Option<&mut Engine> {
+        Some(&mut self.engine)
+    }
+}
+
+/// Plays a uniformly random legal move.
+pub struct RandomPlayer {
+    name: String,
+    state: u64,
+}
+
+impl RandomPlayer {
+    /// The same seed gives the same moves.
+    pub fn new(name: &str, seed: u64) -> Self {
+        RandomPlayer {
+            name: name.to_string(),
+            state: mix(seed),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+impl Player for RandomPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError> {
+        let moves = board.generate_legal_moves(board.turn());
+        if moves.is_empty() {
+            return Err(PlayerError::NoMove);
+        }
+        let index = (self.next() % moves.len() as u64) as usize;
+        Ok(Command::Move(moves[index]))
+    }
+}
+
+/// One SplitMix64 step, so that nearby seeds start far apart. xorshift
+/// gets stuck on zero, which SplitMix gives for just one seed.
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)).max(1)
+}
+
+/// Plays a fixed list of moves, in coordinates or SAN, then runs out.
+pub struct ScriptedPlayer {
+    name: String,
+    moves: VecDeque<String>,
+}
+
+impl ScriptedPlayer {
+    pub fn new(name: &str, moves: &[&str]) -> Self {
+        ScriptedPlayer {
+            name: name.to_string(),
+            moves: moves.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError> {
+        let text = self.moves.pop_front().ok_or(PlayerError::NoMove)?;
+        let legal = board.generate_legal_moves(board.turn());
+        let mv = UI::parse_input(board, &text)
+            .ok()
+            .filter(|mv| legal.contains(mv))
+            .ok_or(PlayerError::IllegalMove(text))?;
+        Ok(Command::Move(mv))
+    }
+}
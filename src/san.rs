@@ -0,0 +1,217 @@
+use crate::game::Board;
+use crate::types::{Color, Move, PieceKind, Square};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SanError {
+    /// The text is not shaped like a SAN move at all.
+    Syntax(String),
+    /// Well-formed, but no legal move in this position matches.
+    Illegal(String),
+    /// More than one legal move matches; a file or rank is missing.
+    Ambiguous(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Syntax(s) => write!(f, "'{}' is not valid SAN", s),
+            SanError::Illegal(s) => write!(f, "'{}' is not a legal move", s),
+            SanError::Ambiguous(s) => write!(f, "'{}' is ambiguous", s),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+impl Board {
+    /// Parses a SAN move such as "Nf3", "exd5", "O-O" or "e8=Q+" against the
+    /// current position. Check and annotation suffixes are ignored.
+    pub fn parse_san(&self, text: &str) -> Result<Move, SanError> {
+        let syntax = || SanError::Syntax(text.to_string());
+        let san = text.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        let castle_file = match san {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            let rank = match self.turn() {
+                Color::White => 0,
+                Color::Black => 7,
+            };
+            return self.unique_match(text, |m| {
+                self.get(m.from).map(|p| p.kind) == Some(PieceKind::King)
+                    && m.from.0 == 4
+                    && m.from.1 == rank
+                    && m.to == Square(file, rank)
+            });
+        }
+
+        let mut rest = san;
+        let mut promotion = None;
+        if let Some((head, promo)) = rest.rsplit_once('=') {
+            promotion = Some(piece_from_letter(promo).ok_or_else(syntax)?);
+            rest = head;
+        } else if let Some(last) = rest.chars().last()
+            && let Some(kind) = piece_from_letter(&last.to_string())
+        {
+            promotion = Some(kind);
+            rest = &rest[..rest.len() - 1];
+        }
+        if matches!(promotion, Some(PieceKind::Pawn | PieceKind::King)) {
+            return Err(syntax());
+        }
+
+        if rest.len() < 2 || !rest.is_char_boundary(rest.len() - 2) {
+            return Err(syntax());
+        }
+        let to = Square::parse(&rest[rest.len() - 2..]).ok_or_else(syntax)?;
+        rest = &rest[..rest.len() - 2];
+
+        let kind = match rest.chars().next() {
+            Some(c) if c.is_ascii_uppercase() => {
+                rest = &rest[1..];
+                piece_from_letter(&c.to_string()).ok_or_else(syntax)?
+            }
+            _ => PieceKind::Pawn,
+        };
+        if kind != PieceKind::Pawn && promotion.is_some() {
+            return Err(syntax());
+        }
+
+        let rest = rest.strip_suffix('x').unwrap_or(rest);
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in rest.chars() {
+            match c {
+                'a'..='h' if from_file.is_none() && from_rank.is_none() => {
+                    from_file = Some(c as u8 - b'a')
+                }
+                '1'..='8' if from_rank.is_none() => from_rank = Some(c as u8 - b'1'),
+                _ => return Err(syntax()),
+            }
+        }
+
+        self.unique_match(text, |m| {
+            m.to == to
+                && m.promotion == promotion
+                && self.get(m.from).map(|p| p.kind) == Some(kind)
+                && from_file.is_none_or(|f| m.from.0 == f)
+                && from_rank.is_none_or(|r| m.from.1 == r)
+        })
+    }
+
+    fn unique_match(&self, text: &str, matches: impl Fn(&Move) -> bool) -> Result<Move, SanError> {
+        let candidates: Vec<Move> = self
+            .generate_legal_moves(self.turn())
+            .into_iter()
+            .filter(matches)
+            .collect();
+        match candidates.as_slice() {
+            [mv] => Ok(*mv),
+            [] => Err(SanError::Illegal(text.to_string())),
+            _ => Err(SanError::Ambiguous(text.to_string())),
+        }
+    }
+
+    /// Standard Algebraic Notation for `mv`, which must be legal in this
+    /// position, e.g. "Nbd7", "exd6", "O-O-O" or "e8=Q+".
+    pub fn to_san(&self, mv: Move) -> String {
+        let piece = match self.get(mv.from) {
+            Some(p) => p,
+            None => return mv.to_string(),
+        };
+
+        let mut san = String::new();
+        let file_diff = mv.to.0 as i8 - mv.from.0 as i8;
+
+        if piece.kind == PieceKind::King && file_diff.abs() == 2 {
+            san.push_str(if file_diff > 0 { "O-O" } else { "O-O-O" });
+        } else {
+            let is_capture = self.get(mv.to).is_some()
+                || (piece.kind == PieceKind::Pawn && mv.from.0 != mv.to.0);
+
+            if piece.kind == PieceKind::Pawn {
+                if is_capture {
+                    san.push((mv.from.0 + b'a') as char);
+                }
+            } else {
+                san.push(piece_letter(piece.kind));
+                san.push_str(&self.disambiguation(mv));
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_string());
+
+            if let Some(kind) = mv.promotion {
+                san.push('=');
+                san.push(piece_letter(kind));
+            }
+        }
+
+        let mut after = self.clone();
+        if after.make_move(mv) && after.is_in_check(after.turn()) {
+            if after.generate_legal_moves(after.turn()).is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+
+        san
+    }
+
+    /// The file, rank or full square of the origin, whichever is the least
+    /// needed to tell `mv` apart from other same-piece moves to its target.
+    fn disambiguation(&self, mv: Move) -> String {
+        let kind = self.get(mv.from).map(|p| p.kind);
+        let rivals: Vec<Move> = self
+            .generate_legal_moves(self.turn())
+            .into_iter()
+            .filter(|m| {
+                m.to == mv.to && m.from != mv.from && self.get(m.from).map(|p| p.kind) == kind
+            })
+            .collect();
+
+        if rivals.is_empty() {
+            return String::new();
+        }
+
+        let file = (mv.from.0 + b'a') as char;
+        let rank = (mv.from.1 + b'1') as char;
+        if rivals.iter().all(|m| m.from.0 != mv.from.0) {
+            file.to_string()
+        } else if rivals.iter().all(|m| m.from.1 != mv.from.1) {
+            rank.to_string()
+        } else {
+            format!("{}{}", file, rank)
+        }
+    }
+}
+
+fn piece_from_letter(letter: &str) -> Option<PieceKind> {
+    match letter {
+        "P" => Some(PieceKind::Pawn),
+        "N" => Some(PieceKind::Knight),
+        "B" => Some(PieceKind::Bishop),
+        "R" => Some(PieceKind::Rook),
+        "Q" => Some(PieceKind::Queen),
+        "K" => Some(PieceKind::King),
+        _ => None,
+    }
+}
+
+fn piece_letter(kind: PieceKind) -> char {
+    match kind {
+        PieceKind::Pawn => 'P',
+        PieceKind::Knight => 'N',
+        PieceKind::Bishop => 'B',
+        PieceKind::Rook => 'R',
+        PieceKind::Queen => 'Q',
+        PieceKind::King => 'K',
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+
+/// z for a two-sided 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+/// Results of a match from the first player's point of view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// An Elo difference with the half-width of its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EloEstimate {
+    pub diff: f64,
+    pub margin: f64,
+}
+
+impl fmt::Display for EloEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:+.1} +/- {:.1}", self.diff, self.margin)
+    }
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, counting a draw as half a point.
+    pub fn score(&self) -> Option<f64> {
+        let games = self.games();
+        if games == 0 {
+            return None;
+        }
+        Some((self.wins as f64 + self.draws as f64 / 2.0) / games as f64)
+    }
+
+    /// The variance of a single game's score.
+    fn variance(&self) -> Option<f64> {
+        let games = self.games() as f64;
+        let p = self.score()?;
+        let deviations = self.wins as f64 * (1.0 - p).powi(2)
+            + self.draws as f64 * (0.5 - p).powi(2)
+            + self.losses as f64 * p.powi(2);
+        Some(deviations / games)
+    }
+
+    /// The Elo difference that explains the score. There is none for a
+    /// perfect or zero score, which no finite difference predicts.
+    pub fn elo(&self) -> Option<EloEstimate> {
+        let p = self.score()?;
+        if p <= 0.0 || p >= 1.0 {
+            return None;
+        }
+        let error = (self.variance()? / self.games() as f64).sqrt();
+        // An interval reaching past 0 or 1 has no finite bound on that side.
+        let low = elo_from_score((p - Z_95 * error).max(0.0));
+        let high = elo_from_score((p + Z_95 * error).min(1.0));
+        Some(EloEstimate {
+            diff: elo_from_score(p),
+            margin: (high - low) / 2.0,
+        })
+    }
+}
+
+impl fmt::Display for MatchScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// The expected score against an opponent `elo` points weaker.
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The inverse of [`score_from_elo`]; infinite at 0 and 1.
+pub fn elo_from_score(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+/// A sequential probability ratio test of "the first player is `elo0`
+/// stronger" (H0) against "it is `elo1` stronger" (H1), with error rates
+/// `alpha` and `beta`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtVerdict {
+    AcceptH0,
+    AcceptH1,
+    /// Not enough games to decide yet.
+    Continue,
+}
+
+impl fmt::Display for SprtVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SprtVerdict::AcceptH0 => write!(f, "H0 accepted"),
+            SprtVerdict::AcceptH1 => write!(f, "H1 accepted"),
+            SprtVerdict::Continue => write!(f, "inconclusive"),
+        }
+    }
+}
+
+impl Sprt {
+    /// The log-likelihood ratio at which the test accepts H0 and H1.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// The log-likelihood ratio of H1 over H0, using the normal
+    /// approximation to the distribution of the mean score.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        let (Some(p), Some(variance)) = (score.score(), score.variance()) else {
+            return 0.0;
+        };
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+        (s1 - s0) * (2.0 * p - s0 - s1) * score.games() as f64 / (2.0 * variance)
+    }
+
+    pub fn verdict(&self, score: &MatchScore) -> SprtVerdict {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtVerdict::AcceptH1
+        } else if llr <= lower {
+            SprtVerdict::AcceptH0
+        } else {
+            SprtVerdict::Continue
+        }
+    }
+}
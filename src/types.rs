@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Color {
     White,
@@ -28,4 +30,44 @@ pub struct Square(pub u8, pub u8);
 pub struct Move {
     pub from: Square,
     pub to: Square,
+    pub promotion: Option<PieceKind>,
+}
+
+impl Square {
+    /// Parses a square name such as "e4".
+    pub fn parse(text: &str) -> Option<Square> {
+        let bytes = text.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+        let file = match bytes[0] {
+            b'a'..=b'h' => bytes[0] - b'a',
+            _ => return None,
+        };
+        let rank = match bytes[1] {
+            b'1'..=b'8' => bytes[1] - b'1',
+            _ => return None,
+        };
+        Some(Square(file, rank))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", (self.0 + b'a') as char, (self.1 + b'1') as char)
+    }
+}
+
+/// UCI long algebraic notation, e.g. "e2e4" or "e7e8n".
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        match self.promotion {
+            Some(PieceKind::Queen) => write!(f, "q"),
+            Some(PieceKind::Rook) => write!(f, "r"),
+            Some(PieceKind::Bishop) => write!(f, "b"),
+            Some(PieceKind::Knight) => write!(f, "n"),
+            _ => Ok(()),
+        }
+    }
 }
@@ -1,10 +1,30 @@
 use crate::game::Board;
+use crate::san::SanError;
 use crate::types::{Color, Move, Piece, PieceKind, Square};
 use std::io::{self, Write};
 
 pub struct UI;
 
+/// Candidate lines shown by a bare "analyze".
+pub const DEFAULT_ANALYSIS_LINES: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Move(Move),
+    Undo,
+    Redo,
+    Save(String),
+    Load(String),
+    /// Show the engine's best lines, this many of them.
+    Analyze(u32),
+    /// Claim a threefold repetition or fifty-move draw.
+    ClaimDraw,
+    Quit,
+    Invalid(String),
+}
+
 impl UI {
+    #[allow(non_snake_case)]
     pub fn readMode() -> u8 {
         loop {
             print!("Enter mode: ");
@@ -45,10 +65,11 @@ impl UI {
         }
 
         println!("    a   b   c   d   e   f   g   h\n");
-        println!("Turn: {:?}\n", board.turn);
+        println!("Turn: {:?}\n", board.turn());
     }
 
-    pub fn readMove() -> Option<Move> {
+    #[allow(non_snake_case)]
+    pub fn readMove(board: &Board) -> Option<Move> {
         print!("Enter move: ");
         io::stdout().flush().unwrap();
 
@@ -57,16 +78,67 @@ impl UI {
             return None;
         }
 
-        let input = input.trim().to_lowercase();
-        if input == "q" {
+        let input = input.trim();
+        if input.eq_ignore_ascii_case("q") {
             return None;
         }
 
-        Self::parseMove(&input)
+        Self::parse_input(board, input).ok()
+    }
+
+    /// Reads a move or one of the commands "undo", "redo", "save <file>",
+    /// "load <file>", "analyze [lines]", "draw" and "q".
+    pub fn read_command(board: &Board) -> Command {
+        print!("Enter move: ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => return Command::Quit,
+            Ok(_) => {}
+        }
+
+        let input = input.trim();
+        if let Some((cmd, path)) = input.split_once(char::is_whitespace) {
+            let path = path.trim().to_string();
+            if cmd.eq_ignore_ascii_case("save") {
+                return Command::Save(path);
+            }
+            if cmd.eq_ignore_ascii_case("load") {
+                return Command::Load(path);
+            }
+            if cmd.eq_ignore_ascii_case("analyze") {
+                return match path.parse() {
+                    Ok(lines) if lines > 0 => Command::Analyze(lines),
+                    _ => Command::Invalid(format!("'{}' is not a number of lines", path)),
+                };
+            }
+        }
+
+        match input.to_lowercase().as_str() {
+            "q" => Command::Quit,
+            "undo" => Command::Undo,
+            "redo" => Command::Redo,
+            "analyze" => Command::Analyze(DEFAULT_ANALYSIS_LINES),
+            "draw" => Command::ClaimDraw,
+            _ => match Self::parse_input(board, input) {
+                Ok(m) => Command::Move(m),
+                Err(e) => Command::Invalid(e.to_string()),
+            },
+        }
     }
 
+    /// Accepts coordinates ("g1f3", "e7e8q") as well as SAN ("Nf3", "e8=Q").
+    pub fn parse_input(board: &Board, text: &str) -> Result<Move, SanError> {
+        match Self::parseMove(&text.to_lowercase()) {
+            Some(m) => Ok(m),
+            None => board.parse_san(text),
+        }
+    }
+
+    #[allow(non_snake_case)]
     pub fn parseMove(text: &str) -> Option<Move> {
-        if text.len() != 4 {
+        if text.len() != 4 && text.len() != 5 {
             return None;
         }
 
@@ -77,9 +149,19 @@ impl UI {
         let f2 = Self::file_to_index(bytes[2] as char)?;
         let r2 = Self::rank_to_index(bytes[3] as char)?;
 
+        let promotion = match bytes.get(4) {
+            None => None,
+            Some(b'q') => Some(PieceKind::Queen),
+            Some(b'r') => Some(PieceKind::Rook),
+            Some(b'b') => Some(PieceKind::Bishop),
+            Some(b'n') => Some(PieceKind::Knight),
+            Some(_) => return None,
+        };
+
         Some(Move {
             from: Square(f1, r1),
             to: Square(f2, r2),
+            promotion,
         })
     }
 
@@ -121,11 +203,14 @@ impl UI {
         input
     }
 
+    /// A line of UCI moves from this position in numbered SAN, e.g.
+    /// "12... Nc6 13. Bb5". Stops at the first move that is not legal.
+    pub fn line_to_san(board: &Board, moves: &[String]) -> String {
+        let moves: Vec<Move> = moves.iter().map_while(|uci| Self::parseMove(uci)).collect();
+        board.numbered_san(&moves).join(" ")
+    }
+
     pub fn move_to_string(m: Move) -> String {
-        let f1 = (m.from.0 + b'a') as char;
-        let r1 = (m.from.1 + b'1') as char;
-        let f2 = (m.to.0 + b'a') as char;
-        let r2 = (m.to.1 + b'1') as char;
-        format!("{}{}{}{}", f1, r1, f2, r2)
+        m.to_string()
     }
 }
@@ -0,0 +1,47 @@
+use crate::bitboard;
+use crate::types::{Color, PieceKind, Square};
+
+const PIECE_KEYS: usize = 2 * 6 * 64;
+const SIDE_KEY: usize = PIECE_KEYS;
+const CASTLING_KEYS: usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+
+/// Fixed pseudo-random keys, so hashes are stable across runs and builds.
+const KEYS: [u64; EN_PASSANT_KEYS + 8] = generate_keys();
+
+pub fn piece(color: Color, kind: PieceKind, sq: Square) -> u64 {
+    KEYS[(color as usize * 6 + kind as usize) * 64 + bitboard::index(sq)]
+}
+
+/// Toggled whenever Black is to move.
+pub fn side_to_move() -> u64 {
+    KEYS[SIDE_KEY]
+}
+
+/// Rights in the order white kingside, white queenside, black kingside,
+/// black queenside.
+pub fn castling(rights: [bool; 4]) -> u64 {
+    (0..4)
+        .filter(|&i| rights[i])
+        .fold(0, |hash, i| hash ^ KEYS[CASTLING_KEYS + i])
+}
+
+pub fn en_passant_file(file: u8) -> u64 {
+    KEYS[EN_PASSANT_KEYS + file as usize]
+}
+
+/// SplitMix64, seeded with an arbitrary constant.
+const fn generate_keys() -> [u64; EN_PASSANT_KEYS + 8] {
+    let mut keys = [0; EN_PASSANT_KEYS + 8];
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
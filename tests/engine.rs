@@ -0,0 +1,641 @@
+use chess_rust::engine::{
+    Bound, Engine, EngineError, EngineEvent, OptionType, Score, SearchInfo, SearchLimits,
+};
+use chess_rust::game::Board;
+use chess_rust::match_runner::{Adjudication, Termination, play_game};
+use chess_rust::pgn::GameResult;
+use chess_rust::player::{EnginePlayer, MAX_RESTARTS, Player, PlayerError, RandomPlayer};
+use chess_rust::ui::{Command, UI};
+use std::time::{Duration, Instant};
+
+/// The scripted engine in src/bin/mock_uci.rs.
+const MOCK: &str = env!("CARGO_BIN_EXE_mock_uci");
+
+fn mock(script: &[&str]) -> Engine {
+    Engine::spawn(MOCK, script).expect("mock engine starts")
+}
+
+fn spawn_error(script: &[&str], timeout: Duration) -> EngineError {
+    match Engine::spawn_with_timeout(MOCK, script, timeout) {
+        Ok(_) => panic!("handshake should fail"),
+        Err(e) => e,
+    }
+}
+
+fn after(moves: &[&str]) -> Board {
+    let mut board = Board::new();
+    for text in moves {
+        let mv = UI::parse_input(&board, text).unwrap();
+        assert!(board.make_move(mv));
+    }
+    board
+}
+
+/// The text of the next "info string" line.
+fn next_string(engine: &mut Engine) -> String {
+    match engine.recv_timeout(Duration::from_secs(5)) {
+        Ok(EngineEvent::Info(SearchInfo {
+            string: Some(text), ..
+        })) => text,
+        other => panic!("expected info string, got {:?}", other),
+    }
+}
+
+/// Every command the mock has received, for scripts with "on history".
+fn history(engine: &mut Engine) -> Vec<String> {
+    engine.send("history").unwrap();
+    let mut received = Vec::new();
+    while received.last().is_none_or(|last| last != "history") {
+        received.push(next_string(engine));
+    }
+    received
+}
+
+#[test]
+fn handshake_collects_options() {
+    let engine = mock(&[
+        "on uci",
+        "id name Mock 1.0",
+        "id author Nobody",
+        "option name Hash type spin default 16 min 1 max 1024",
+        "option name Style type combo default Normal var Solid var Normal var Risky",
+        "option name Clear Hash type button",
+        "uciok",
+    ]);
+    let hash = engine.options().get("hash").unwrap();
+    assert_eq!(hash.kind, OptionType::Spin);
+    assert_eq!((hash.min, hash.max), (Some(1), Some(1024)));
+    let style = engine.options().get("Style").unwrap();
+    assert_eq!(style.vars, ["Solid", "Normal", "Risky"]);
+    assert_eq!(engine.options().iter().count(), 3);
+}
+
+#[test]
+fn banner_and_malformed_lines_are_ignored_in_the_handshake() {
+    let engine = mock(&[
+        "Mock engine, built today",
+        "",
+        "on uci",
+        "option name Broken",
+        "option type spin",
+        "info string not yet",
+        "uciokay",
+        "uciok",
+    ]);
+    assert_eq!(engine.options().iter().count(), 0);
+}
+
+#[test]
+fn missing_executable_is_reported() {
+    match Engine::spawn("/no/such/engine", &[]) {
+        Err(EngineError::NotFound(path)) => assert_eq!(path, "/no/such/engine"),
+        other => panic!("expected NotFound, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn silent_engine_times_out() {
+    let timeout = Duration::from_millis(200);
+    let started = Instant::now();
+    assert_eq!(
+        spawn_error(&["on uci", "id name Mock"], timeout),
+        EngineError::Timeout(timeout)
+    );
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn engine_exiting_during_handshake_reports_its_code() {
+    assert_eq!(
+        spawn_error(&["on uci", "exit 3"], Duration::from_secs(5)),
+        EngineError::Exited(Some(3))
+    );
+    assert_eq!(
+        spawn_error(&["exit 0"], Duration::from_secs(5)),
+        EngineError::Exited(Some(0))
+    );
+}
+
+#[test]
+fn search_output_before_uciok_is_a_protocol_error() {
+    let error = spawn_error(&["on uci", "readyok"], Duration::from_secs(5));
+    assert!(matches!(error, EngineError::Protocol(_)));
+    let error = spawn_error(&["on uci", "bestmove e2e4"], Duration::from_secs(5));
+    assert!(matches!(error, EngineError::Protocol(_)));
+}
+
+#[test]
+fn search_collects_info_and_best_move() {
+    let mut engine = mock(&[
+        "on go",
+        "info depth 1 score cp 10 pv e2e4 e7e5",
+        "info depth 2 seldepth 4 multipv 1 score cp 25 upperbound nodes 1200 nps 60000 time 20 pv d2d4 d7d5",
+        "info depth 2 multipv 2 score mate -3 pv a2a3",
+        "info currmove e2e4 currmovenumber 1",
+        "info depth x score cp y nodes",
+        "info string thinking hard",
+        "bestmove d2d4 ponder d7d5",
+    ]);
+    let mut infos = 0;
+    let result = engine
+        .search(&Board::new(), &SearchLimits::depth(2), |_| infos += 1)
+        .unwrap();
+    assert_eq!(infos, 6);
+    assert_eq!(result.best.as_deref(), Some("d2d4"));
+    assert_eq!(result.ponder.as_deref(), Some("d7d5"));
+    // The last scored line of the main variation.
+    assert_eq!(result.info.depth, Some(2));
+    assert_eq!(result.info.seldepth, Some(4));
+    assert_eq!(result.info.score, Some(Score::Cp(25)));
+    assert_eq!(result.info.bound, Bound::Upper);
+    assert_eq!(result.info.nodes, Some(1200));
+    assert_eq!(result.info.time, Some(Duration::from_millis(20)));
+    assert_eq!(result.info.pv, ["d2d4", "d7d5"]);
+}
+
+#[test]
+fn bare_bestmove_has_no_move() {
+    let mut engine = mock(&["on go", "bestmove"]);
+    let result = engine
+        .search(&Board::new(), &SearchLimits::depth(1), |_| {})
+        .unwrap();
+    assert_eq!(result.best, None);
+    assert_eq!(result.ponder, None);
+}
+
+#[test]
+fn positions_are_sent_as_fen_and_moves() {
+    let mut engine = mock(&["on position", "echo", "on go", "echo"]);
+    engine.set_position(&after(&["e4", "c5"])).unwrap();
+    assert_eq!(
+        next_string(&mut engine),
+        "position startpos moves e2e4 c7c5"
+    );
+
+    let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+    let mut board = Board::from_fen(fen).unwrap();
+    assert!(board.make_move(UI::parse_input(&board, "O-O").unwrap()));
+    engine.set_position(&board).unwrap();
+    assert_eq!(
+        next_string(&mut engine),
+        format!("position fen {} moves e1g1", fen)
+    );
+
+    let limits = SearchLimits {
+        wtime: Some(Duration::from_secs(60)),
+        btime: Some(Duration::from_secs(50)),
+        winc: Some(Duration::from_secs(1)),
+        movestogo: Some(20),
+        ..SearchLimits::default()
+    };
+    engine.send(&limits.go_command()).unwrap();
+    assert_eq!(
+        next_string(&mut engine),
+        "go wtime 60000 btime 50000 winc 1000 movestogo 20"
+    );
+}
+
+#[test]
+fn options_are_checked_before_they_are_sent() {
+    let mut engine = mock(&[
+        "on uci",
+        "option name Hash type spin default 16 min 1 max 1024",
+        "option name Clear Hash type button",
+        "uciok",
+        "on setoption",
+        "echo",
+    ]);
+    assert_eq!(
+        engine.set_option("Threads", "2"),
+        Err(EngineError::UnknownOption("Threads".to_string()))
+    );
+    assert!(matches!(
+        engine.set_option("Hash", "4096"),
+        Err(EngineError::InvalidOptionValue { .. })
+    ));
+    engine.set_option("hash", "64").unwrap();
+    assert_eq!(next_string(&mut engine), "setoption name Hash value 64");
+    engine.set_option("Clear Hash", "").unwrap();
+    assert_eq!(next_string(&mut engine), "setoption name Clear Hash");
+}
+
+#[test]
+fn new_game_waits_for_the_engine() {
+    let mut engine = mock(&[
+        "on isready",
+        "sleep 100",
+        "info string ready soon",
+        "readyok",
+    ]);
+    let started = Instant::now();
+    engine.new_game().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert_eq!(engine.try_recv(), Ok(None));
+}
+
+#[test]
+fn crash_during_search_reports_the_exit_code() {
+    let mut engine = mock(&["on go", "info depth 1 score cp 5 pv e2e4", "exit 7"]);
+    let result = engine.search(&Board::new(), &SearchLimits::depth(5), |_| {});
+    assert_eq!(result, Err(EngineError::Exited(Some(7))));
+    assert_eq!(engine.send("isready"), Err(EngineError::Exited(Some(7))));
+}
+
+#[test]
+fn crash_on_a_later_command_is_reported_there() {
+    let mut engine = mock(&["on go", "bestmove e2e4", "on go", "exit 9"]);
+    let limits = SearchLimits::depth(1);
+    let first = engine.search(&Board::new(), &limits, |_| {}).unwrap();
+    assert_eq!(first.best.as_deref(), Some("e2e4"));
+    let second = engine.search(&after(&["e4"]), &limits, |_| {});
+    assert_eq!(second, Err(EngineError::Exited(Some(9))));
+}
+
+#[test]
+fn overrunning_search_is_stopped() {
+    // The engine ignores its move time and only answers "stop".
+    let mut engine = mock(&[
+        "on go",
+        "info depth 1 score cp 0 pv g1f3",
+        "on stop",
+        "bestmove g1f3",
+    ]);
+    let started = Instant::now();
+    let result = engine
+        .search(
+            &Board::new(),
+            &SearchLimits::movetime(Duration::from_millis(50)),
+            |_| {},
+        )
+        .unwrap();
+    assert_eq!(result.best.as_deref(), Some("g1f3"));
+    assert!(started.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn endless_depth_search_times_out() {
+    // The engine never finishes its depth and ignores "stop".
+    let mut engine = mock(&[
+        "on go depth",
+        "info depth 1 score cp 0 pv e2e4",
+        "info depth 2 score cp 10 pv e2e4 e7e5",
+    ]);
+    let timeout = Duration::from_millis(200);
+    engine.set_search_timeout(Some(timeout));
+    let started = Instant::now();
+    let result = engine.search(&Board::new(), &SearchLimits::depth(12), |_| {});
+    assert!(matches!(result, Err(EngineError::Timeout(_))));
+    assert!(result.unwrap_err().is_crash());
+    // The search timeout, then the time an engine gets to answer "stop".
+    assert!(started.elapsed() >= timeout);
+    assert!(started.elapsed() < timeout + Duration::from_secs(7));
+}
+
+#[test]
+fn missing_readyok_times_out_on_recv() {
+    let mut engine = mock(&["on isready"]);
+    engine.send("isready").unwrap();
+    let timeout = Duration::from_millis(100);
+    assert_eq!(
+        engine.recv_timeout(timeout),
+        Err(EngineError::Timeout(timeout))
+    );
+}
+
+#[test]
+fn analysis_keeps_the_latest_of_each_line() {
+    let mut engine = mock(&[
+        "on uci",
+        "option name MultiPV type spin default 1 min 1 max 500",
+        "uciok",
+        "on go",
+        "info depth 1 multipv 1 score cp 20 pv e2e4",
+        "info depth 1 multipv 2 score cp 10 pv d2d4",
+        "info depth 2 multipv 1 score cp 30 pv e2e4 e7e5",
+        "on stop",
+        "bestmove e2e4 ponder e7e5",
+    ]);
+    let limits = SearchLimits {
+        infinite: true,
+        ..SearchLimits::default()
+    };
+    let mut analysis = engine.analyze(&Board::new(), 2, &limits).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while analysis.lines().get(&1).and_then(|info| info.depth) != Some(2) {
+        assert!(Instant::now() < deadline, "analysis never reached depth 2");
+        analysis.wait(Duration::from_millis(50)).unwrap();
+    }
+    assert_eq!(analysis.lines().len(), 2);
+    assert_eq!(analysis.lines()[&2].pv, ["d2d4"]);
+    let result = analysis.stop().unwrap();
+    assert_eq!(result.best.as_deref(), Some("e2e4"));
+}
+
+#[test]
+fn analysis_clamps_and_then_restores_multipv() {
+    let script = [
+        "on uci",
+        "option name MultiPV type spin default 1 min 1 max 5",
+        "uciok",
+        "on history",
+        "history",
+        "on stop",
+        "bestmove e2e4",
+    ];
+    let limits = SearchLimits {
+        infinite: true,
+        ..SearchLimits::default()
+    };
+
+    let mut engine = mock(&script);
+    engine.set_option("MultiPV", "3").unwrap();
+    engine
+        .analyze(&Board::new(), 600, &limits)
+        .unwrap()
+        .stop()
+        .unwrap();
+    engine.restart().unwrap();
+    assert_eq!(
+        history(&mut engine),
+        [
+            "uci",
+            "setoption name MultiPV value 3",
+            "isready",
+            "history"
+        ]
+    );
+
+    let mut engine = mock(&script);
+    engine
+        .analyze(&Board::new(), 600, &limits)
+        .unwrap()
+        .stop()
+        .unwrap();
+    assert_eq!(
+        history(&mut engine),
+        [
+            "uci",
+            "setoption name MultiPV value 5",
+            "position startpos",
+            "go infinite",
+            "stop",
+            "setoption name MultiPV value 1",
+            "history"
+        ]
+    );
+    engine.restart().unwrap();
+    assert_eq!(history(&mut engine), ["uci", "isready", "history"]);
+}
+
+/// A fresh path for the mock's `exit-once`, unique to this test run.
+fn marker(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("mock-uci-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+fn engine_player(script: &[&str]) -> EnginePlayer {
+    EnginePlayer::new("Mock", mock(script), SearchLimits::depth(1))
+}
+
+#[test]
+fn restart_sets_the_same_options() {
+    let mut engine = mock(&[
+        "on uci",
+        "option name Hash type spin default 16 min 1 max 1024",
+        "option name Clear Hash type button",
+        "uciok",
+        "on history",
+        "history",
+    ]);
+    engine.set_option("Hash", "32").unwrap();
+    engine.set_option("Clear Hash", "").unwrap();
+    engine.set_option("Hash", "64").unwrap();
+    engine.restart().unwrap();
+    assert_eq!(
+        history(&mut engine),
+        ["uci", "setoption name Hash value 64", "isready", "history"]
+    );
+}
+
+#[test]
+fn engine_moves_are_checked_against_the_legal_moves() {
+    let board = after(&["e4"]);
+    let mut player = engine_player(&["on go", "bestmove e7e4"]);
+    assert_eq!(
+        player.choose(&board),
+        Err(PlayerError::IllegalMove("e7e4".to_string()))
+    );
+    assert!(player.last_info().is_none());
+
+    let mut player = engine_player(&["on go", "bestmove nonsense"]);
+    assert_eq!(
+        player.choose(&board),
+        Err(PlayerError::IllegalMove("nonsense".to_string()))
+    );
+
+    let mut player = engine_player(&["on go", "bestmove e7e5"]);
+    assert_eq!(
+        player.choose(&board),
+        Ok(Command::Move(UI::parseMove("e7e5").unwrap()))
+    );
+}
+
+#[test]
+fn no_move_from_the_engine_is_an_error() {
+    for reply in ["bestmove (none)", "bestmove 0000", "bestmove"] {
+        let mut player = engine_player(&["on go", reply]);
+        assert_eq!(player.choose(&Board::new()), Err(PlayerError::NoMove));
+    }
+}
+
+#[test]
+fn crashed_engine_is_restarted_and_sent_the_position_again() {
+    let once = marker("restart");
+    let mut player = engine_player(&[
+        "on history",
+        "history",
+        "on go",
+        &format!("exit-once {} 4", once),
+        "bestmove d7d5",
+    ]);
+    let board = after(&["d4"]);
+    assert_eq!(
+        player.choose(&board),
+        Ok(Command::Move(UI::parseMove("d7d5").unwrap()))
+    );
+    assert_eq!(
+        history(player.engine().unwrap()),
+        [
+            "uci",
+            "isready",
+            "position startpos moves d2d4",
+            "go depth 1",
+            "history"
+        ]
+    );
+    let _ = std::fs::remove_file(once);
+}
+
+#[test]
+fn engine_that_keeps_crashing_forfeits() {
+    let mut player = engine_player(&["on go", "exit 5"]);
+    assert_eq!(
+        player.choose(&Board::new()),
+        Err(PlayerError::Crashed {
+            restarts: MAX_RESTARTS,
+            error: EngineError::Exited(Some(5)),
+        })
+    );
+}
+
+#[test]
+fn crashing_engine_forfeits_the_game() {
+    let mut white = engine_player(&["on go", "exit 5"]);
+    let mut black = RandomPlayer::new("Random", 1);
+    let record = play_game(
+        &Board::new(),
+        &mut white,
+        &mut black,
+        &Adjudication::default(),
+    );
+    assert_eq!(record.result, GameResult::BlackWins);
+    let Termination::Forfeit(reason) = record.termination else {
+        panic!("expected a forfeit");
+    };
+    assert_eq!(
+        reason,
+        "Mock failed: engine exited with code 5, even after 2 restarts"
+    );
+}
+
+/// An engine player with the "Ponder" option on. Its first search plays
+/// e4 expecting e5, the second Nf3 expecting Nc6. Ponder searches only end
+/// on "ponderhit" or "stop".
+fn pondering_player() -> EnginePlayer {
+    let mut player = engine_player(&[
+        "on uci",
+        "option name Ponder type check default false",
+        "uciok",
+        "on history",
+        "history",
+        "on go depth",
+        "info depth 1 score cp 20 pv e2e4 e7e5",
+        "bestmove e2e4 ponder e7e5",
+        "on go depth",
+        "info depth 1 score cp 25 pv g1f3 b8c6",
+        "bestmove g1f3 ponder b8c6",
+        "on go ponder",
+        "on ponderhit",
+        "info depth 1 score cp 25 pv g1f3 b8c6",
+        "bestmove g1f3 ponder b8c6",
+        "on stop",
+        "bestmove e7e5",
+    ]);
+    let engine = player.engine().unwrap();
+    assert!(!engine.ponder_enabled());
+    engine.set_option("Ponder", "true").unwrap();
+    assert!(engine.ponder_enabled());
+    player
+}
+
+fn play_move(player: &mut EnginePlayer, board: &Board, expected: &str) {
+    assert_eq!(
+        player.choose(board),
+        Ok(Command::Move(UI::parseMove(expected).unwrap()))
+    );
+}
+
+#[test]
+fn engine_without_the_ponder_option_does_not_ponder() {
+    let mut player = engine_player(&[
+        "on history",
+        "history",
+        "on go",
+        "bestmove e2e4 ponder e7e5",
+    ]);
+    play_move(&mut player, &Board::new(), "e2e4");
+    assert_eq!(
+        history(player.engine().unwrap()),
+        ["uci", "position startpos", "go depth 1", "history"]
+    );
+}
+
+#[test]
+fn predicted_reply_continues_the_ponder_search() {
+    let mut player = pondering_player();
+    play_move(&mut player, &Board::new(), "e2e4");
+    play_move(&mut player, &after(&["e4", "e5"]), "g1f3");
+    assert_eq!(player.comment().as_deref(), Some("depth 1, score +0.25"));
+    assert_eq!(
+        history(player.engine().unwrap()),
+        [
+            "uci",
+            "setoption name Ponder value true",
+            "position startpos",
+            "go depth 1",
+            "position startpos moves e2e4 e7e5",
+            "go ponder depth 1",
+            "ponderhit",
+            "position startpos moves e2e4 e7e5 g1f3 b8c6",
+            "go ponder depth 1",
+            "history"
+        ]
+    );
+}
+
+#[test]
+fn other_reply_stops_the_ponder_search_and_searches_again() {
+    let mut player = pondering_player();
+    play_move(&mut player, &Board::new(), "e2e4");
+    play_move(&mut player, &after(&["e4", "c5"]), "g1f3");
+    assert_eq!(
+        history(player.engine().unwrap()),
+        [
+            "uci",
+            "setoption name Ponder value true",
+            "position startpos",
+            "go depth 1",
+            "position startpos moves e2e4 e7e5",
+            "go ponder depth 1",
+            "stop",
+            "position startpos moves e2e4 c7c5",
+            "go depth 1",
+            "position startpos moves e2e4 c7c5 g1f3 b8c6",
+            "go ponder depth 1",
+            "history"
+        ]
+    );
+}
+
+#[test]
+fn new_game_and_options_stop_the_ponder_search() {
+    let mut player = pondering_player();
+    play_move(&mut player, &Board::new(), "e2e4");
+    player.new_game().unwrap();
+    play_move(&mut player, &Board::new(), "g1f3");
+    let engine = player.engine().unwrap();
+    engine.set_option("Ponder", "false").unwrap();
+    assert!(!engine.ponder_enabled());
+    assert_eq!(
+        history(engine),
+        [
+            "uci",
+            "setoption name Ponder value true",
+            "position startpos",
+            "go depth 1",
+            "position startpos moves e2e4 e7e5",
+            "go ponder depth 1",
+            "stop",
+            "ucinewgame",
+            "isready",
+            "position startpos",
+            "go depth 1",
+            "position startpos moves g1f3 b8c6",
+            "go ponder depth 1",
+            "stop",
+            "setoption name Ponder value false",
+            "history"
+        ]
+    );
+}
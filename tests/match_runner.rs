@@ -0,0 +1,347 @@
+use chess_rust::engine::{Score, SearchInfo};
+use chess_rust::game::Board;
+use chess_rust::match_runner::{Adjudication, MatchConfig, Termination, play_game, run_match};
+use chess_rust::pgn::GameResult;
+use chess_rust::player::{Player, PlayerError, RandomPlayer, ScriptedPlayer};
+use chess_rust::stats::{MatchScore, Sprt, SprtVerdict, elo_from_score, score_from_elo};
+use chess_rust::types::Color;
+use chess_rust::ui::Command;
+
+/// Plays random moves while claiming a fixed evaluation, given from White's
+/// point of view.
+struct Evaluating {
+    moves: RandomPlayer,
+    white_score: Score,
+    tbhits: Option<u64>,
+    info: SearchInfo,
+}
+
+impl Evaluating {
+    fn new(seed: u64, white_score: Score) -> Self {
+        Evaluating {
+            moves: RandomPlayer::new("evaluating", seed),
+            white_score,
+            tbhits: None,
+            info: SearchInfo::default(),
+        }
+    }
+}
+
+impl Player for Evaluating {
+    fn name(&self) -> &str {
+        self.moves.name()
+    }
+
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError> {
+        let score = match board.turn() {
+            Color::White => self.white_score,
+            Color::Black => -self.white_score,
+        };
+        self.info = SearchInfo {
+            depth: Some(1),
+            score: Some(score),
+            tbhits: self.tbhits,
+            ..SearchInfo::default()
+        };
+        self.moves.choose(board)
+    }
+
+    fn last_info(&self) -> Option<&SearchInfo> {
+        Some(&self.info)
+    }
+}
+
+/// Forfeits every other game.
+struct Flaky {
+    moves: RandomPlayer,
+    forfeit: bool,
+}
+
+impl Player for Flaky {
+    fn name(&self) -> &str {
+        self.moves.name()
+    }
+
+    fn choose(&mut self, board: &Board) -> Result<Command, PlayerError> {
+        if self.forfeit {
+            return Err(PlayerError::NoMove);
+        }
+        self.moves.choose(board)
+    }
+
+    fn new_game(&mut self) -> Result<(), PlayerError> {
+        self.forfeit = !self.forfeit;
+        Ok(())
+    }
+}
+
+fn config(games: u32) -> MatchConfig {
+    MatchConfig {
+        games,
+        openings: Vec::new(),
+        adjudication: Adjudication::default(),
+        sprt: None,
+    }
+}
+
+#[test]
+fn elo_and_score_are_inverse() {
+    assert!((score_from_elo(0.0) - 0.5).abs() < 1e-9);
+    assert!((score_from_elo(400.0) - 10.0 / 11.0).abs() < 1e-9);
+    assert!((elo_from_score(0.75) - 190.85).abs() < 0.01);
+    assert_eq!(elo_from_score(0.5), 0.0);
+}
+
+#[test]
+fn elo_estimate_has_error_bars() {
+    let score = MatchScore {
+        wins: 60,
+        draws: 20,
+        losses: 20,
+    };
+    assert_eq!(score.score(), Some(0.7));
+    let elo = score.elo().unwrap();
+    assert!((elo.diff - 147.2).abs() < 0.1);
+    assert!(elo.margin > 50.0 && elo.margin < 100.0);
+    assert_eq!(score.to_string(), "+60 =20 -20");
+}
+
+#[test]
+fn no_elo_for_a_perfect_or_empty_score() {
+    let perfect = MatchScore {
+        wins: 10,
+        draws: 0,
+        losses: 0,
+    };
+    assert_eq!(perfect.elo(), None);
+    assert_eq!(MatchScore::default().elo(), None);
+    assert_eq!(MatchScore::default().score(), None);
+}
+
+#[test]
+fn sprt_reaches_both_verdicts() {
+    let sprt = Sprt::default();
+    let (lower, upper) = sprt.bounds();
+    assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+    let even = MatchScore {
+        wins: 10,
+        draws: 10,
+        losses: 10,
+    };
+    assert_eq!(sprt.verdict(&even), SprtVerdict::Continue);
+
+    let winning = MatchScore {
+        wins: 3000,
+        draws: 4000,
+        losses: 2000,
+    };
+    assert_eq!(sprt.verdict(&winning), SprtVerdict::AcceptH1);
+
+    let losing = MatchScore {
+        wins: 2000,
+        draws: 4000,
+        losses: 3000,
+    };
+    assert_eq!(sprt.verdict(&losing), SprtVerdict::AcceptH0);
+}
+
+#[test]
+fn random_game_ends_by_the_rules() {
+    let mut white = RandomPlayer::new("white", 1);
+    let mut black = RandomPlayer::new("black", 2);
+    let record = play_game(
+        &Board::new(),
+        &mut white,
+        &mut black,
+        &Adjudication::default(),
+    );
+    assert_eq!(record.termination, Termination::Rules);
+    assert_ne!(record.result, GameResult::Unfinished);
+    assert!(record.board.outcome().is_some() || record.board.claimable_draw().is_some());
+}
+
+#[test]
+fn player_without_a_move_forfeits() {
+    let mut white = ScriptedPlayer::new("white", &["e4", "Nf3"]);
+    let mut black = ScriptedPlayer::new("black", &["e5", "Nc6", "Nf6"]);
+    let record = play_game(
+        &Board::new(),
+        &mut white,
+        &mut black,
+        &Adjudication::default(),
+    );
+    assert_eq!(record.result, GameResult::BlackWins);
+    assert!(
+        matches!(record.termination, Termination::Forfeit(ref reason) if reason.contains("white"))
+    );
+    assert_eq!(record.termination.pgn_tag(), "rules infraction");
+    assert_eq!(record.board.rewind().1.len(), 4);
+}
+
+#[test]
+fn neighbouring_seeds_play_different_moves() {
+    let choices = |seed| {
+        let mut player = RandomPlayer::new("random", seed);
+        (0..8)
+            .map(|_| player.choose(&Board::new()).unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(choices(2), choices(2));
+    assert_ne!(choices(2), choices(3));
+    assert_ne!(choices(0), choices(1));
+}
+
+#[test]
+fn bad_script_entry_is_reported_with_its_text() {
+    let mut player = ScriptedPlayer::new("scripted", &["e5", "e2e5", "e4"]);
+    let board = Board::new();
+    assert_eq!(
+        player.choose(&board),
+        Err(PlayerError::IllegalMove("e5".to_string()))
+    );
+    assert_eq!(
+        player.choose(&board),
+        Err(PlayerError::IllegalMove("e2e5".to_string()))
+    );
+    assert!(matches!(player.choose(&board), Ok(Command::Move(_))));
+    assert_eq!(player.choose(&board), Err(PlayerError::NoMove));
+}
+
+#[test]
+fn move_limit_ends_in_a_draw() {
+    let mut white = RandomPlayer::new("white", 3);
+    let mut black = RandomPlayer::new("black", 4);
+    let adjudication = Adjudication {
+        max_moves: Some(5),
+        ..Adjudication::default()
+    };
+    let record = play_game(&Board::new(), &mut white, &mut black, &adjudication);
+    assert_eq!(record.result, GameResult::Draw);
+    assert_eq!(record.termination, Termination::MoveLimit);
+    assert_eq!(record.board.rewind().1.len(), 10);
+}
+
+#[test]
+fn lopsided_scores_are_adjudicated_as_resignation() {
+    let mut white = Evaluating::new(5, Score::Cp(-800));
+    let mut black = Evaluating::new(6, Score::Cp(-800));
+    let adjudication = Adjudication {
+        resign_score: Some(600),
+        resign_moves: 3,
+        ..Adjudication::default()
+    };
+    let record = play_game(&Board::new(), &mut white, &mut black, &adjudication);
+    assert_eq!(record.result, GameResult::BlackWins);
+    assert_eq!(record.termination, Termination::Resignation);
+    assert_eq!(record.board.rewind().1.len(), 6);
+}
+
+#[test]
+fn level_scores_are_adjudicated_as_a_draw() {
+    let mut white = Evaluating::new(7, Score::Cp(5));
+    let mut black = Evaluating::new(8, Score::Cp(5));
+    let adjudication = Adjudication {
+        draw_score: Some(10),
+        draw_moves: 2,
+        draw_after: 3,
+        ..Adjudication::default()
+    };
+    let record = play_game(&Board::new(), &mut white, &mut black, &adjudication);
+    assert_eq!(record.result, GameResult::Draw);
+    assert_eq!(record.termination, Termination::DrawAdjudication);
+    // Black's second move reaches move 3.
+    assert_eq!(record.board.rewind().1.len(), 4);
+}
+
+#[test]
+fn tablebase_scores_end_the_game() {
+    let start = Board::from_fen("8/8/8/4k3/8/8/3QK3/8 w - - 0 1").unwrap();
+    let mut white = Evaluating::new(9, Score::Mate(10));
+    let mut black = Evaluating::new(10, Score::Mate(10));
+    white.tbhits = Some(1);
+    black.tbhits = Some(1);
+    let adjudication = Adjudication {
+        tablebase_pieces: Some(3),
+        ..Adjudication::default()
+    };
+    let record = play_game(&start, &mut white, &mut black, &adjudication);
+    assert_eq!(record.result, GameResult::WhiteWins);
+    assert_eq!(record.termination, Termination::Tablebase);
+    assert_eq!(record.board.rewind().1.len(), 1);
+}
+
+#[test]
+fn match_alternates_colours_over_each_opening() {
+    let openings = vec![
+        Board::from_epd("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";")
+            .unwrap(),
+        Board::from_epd("rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq -").unwrap(),
+    ];
+    let config = MatchConfig {
+        openings: openings.clone(),
+        ..config(4)
+    };
+    // The first player always forfeits, so only the colours decide the result.
+    let mut first = ScriptedPlayer::new("first", &[]);
+    let mut second = RandomPlayer::new("second", 11);
+
+    let mut games = Vec::new();
+    let score = run_match(&mut first, &mut second, &config, |game, _| {
+        let (start, _) = game.record.board.rewind();
+        games.push((
+            game.round,
+            game.first_is_white,
+            start.to_fen(),
+            game.record.result,
+        ));
+    });
+
+    assert_eq!(
+        score,
+        MatchScore {
+            wins: 0,
+            draws: 0,
+            losses: 4
+        }
+    );
+    let expected = [
+        (1, true, &openings[0], GameResult::BlackWins),
+        (2, false, &openings[0], GameResult::WhiteWins),
+        (3, true, &openings[1], GameResult::BlackWins),
+        (4, false, &openings[1], GameResult::WhiteWins),
+    ];
+    let expected: Vec<_> = expected
+        .into_iter()
+        .map(|(round, first_is_white, opening, result)| {
+            (round, first_is_white, opening.to_fen(), result)
+        })
+        .collect();
+    assert_eq!(games, expected);
+}
+
+#[test]
+fn match_stops_on_an_sprt_verdict() {
+    let config = MatchConfig {
+        adjudication: Adjudication {
+            max_moves: Some(1),
+            ..Adjudication::default()
+        },
+        sprt: Some(Sprt {
+            elo0: 0.0,
+            elo1: 300.0,
+            ..Sprt::default()
+        }),
+        ..config(100)
+    };
+    // Every other game the second player forfeits; the rest are drawn.
+    let mut first = RandomPlayer::new("first", 12);
+    let mut second = Flaky {
+        moves: RandomPlayer::new("second", 13),
+        forfeit: false,
+    };
+    let score = run_match(&mut first, &mut second, &config, |_, _| {});
+    assert!(score.games() < 100);
+    assert_eq!(score.losses, 0);
+    assert_eq!(config.sprt.unwrap().verdict(&score), SprtVerdict::AcceptH1);
+}
@@ -0,0 +1,129 @@
+// Reference counts from https://www.chessprogramming.org/Perft_Results
+
+use chess_rust::fen::STARTING_FEN;
+use chess_rust::game::Board;
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED: &str =
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+fn check(fen: &str, expected: &[u64]) {
+    let board = Board::from_fen(fen).unwrap();
+    for (depth, &nodes) in expected.iter().enumerate() {
+        let depth = depth as u32 + 1;
+        assert_eq!(board.perft(depth), nodes, "{} depth {}", fen, depth);
+    }
+}
+
+#[test]
+fn perft_start_position() {
+    check(STARTING_FEN, &[20, 400, 8_902, 197_281]);
+}
+
+#[test]
+#[ignore]
+fn perft_start_position_deep() {
+    let board = Board::from_fen(STARTING_FEN).unwrap();
+    assert_eq!(board.perft(5), 4_865_609);
+    assert_eq!(board.perft(6), 119_060_324);
+}
+
+#[test]
+fn perft_kiwipete() {
+    check(KIWIPETE, &[48, 2_039, 97_862]);
+}
+
+#[test]
+#[ignore]
+fn perft_kiwipete_deep() {
+    let board = Board::from_fen(KIWIPETE).unwrap();
+    assert_eq!(board.perft(4), 4_085_603);
+    assert_eq!(board.perft(5), 193_690_690);
+}
+
+#[test]
+fn perft_position_3() {
+    check(POSITION_3, &[14, 191, 2_812, 43_238]);
+}
+
+#[test]
+#[ignore]
+fn perft_position_3_deep() {
+    let board = Board::from_fen(POSITION_3).unwrap();
+    assert_eq!(board.perft(5), 674_624);
+    assert_eq!(board.perft(6), 11_030_083);
+}
+
+#[test]
+fn perft_position_4() {
+    check(POSITION_4, &[6, 264, 9_467]);
+    check(POSITION_4_MIRRORED, &[6, 264, 9_467]);
+}
+
+#[test]
+#[ignore]
+fn perft_position_4_deep() {
+    let board = Board::from_fen(POSITION_4).unwrap();
+    assert_eq!(board.perft(4), 422_333);
+    assert_eq!(board.perft(5), 15_833_292);
+}
+
+#[test]
+fn perft_position_5() {
+    check(POSITION_5, &[44, 1_486, 62_379]);
+}
+
+#[test]
+#[ignore]
+fn perft_position_5_deep() {
+    let board = Board::from_fen(POSITION_5).unwrap();
+    assert_eq!(board.perft(4), 2_103_487);
+}
+
+#[test]
+fn perft_position_6() {
+    check(POSITION_6, &[46, 2_079, 89_890]);
+}
+
+#[test]
+#[ignore]
+fn perft_position_6_deep() {
+    let board = Board::from_fen(POSITION_6).unwrap();
+    assert_eq!(board.perft(4), 3_894_594);
+}
+
+#[test]
+fn perft_divide_sums_to_perft() {
+    let board = Board::from_fen(KIWIPETE).unwrap();
+    let divide = board.perft_divide(2);
+    assert_eq!(divide.len(), 48);
+    assert_eq!(divide.iter().map(|(_, n)| n).sum::<u64>(), 2_039);
+    assert!(board.perft_divide(0).is_empty());
+    assert_eq!(board.perft(0), 1);
+}
+
+#[test]
+fn make_and_unmake_restore_the_position() {
+    // Between them these cover castling, en passant and promotions.
+    for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+        let mut board = Board::from_fen(fen).unwrap();
+        let hash = board.hash();
+        for mv in board.generate_legal_moves(board.turn()) {
+            assert!(board.make_move(mv));
+            for reply in board.generate_legal_moves(board.turn()) {
+                let (before_fen, before_hash) = (board.to_fen(), board.hash());
+                assert!(board.make_move(reply));
+                assert_eq!(board.unmake_move(), Some(reply));
+                assert_eq!(board.to_fen(), before_fen, "{} after {}", fen, reply);
+                assert_eq!(board.hash(), before_hash, "{} after {}", fen, reply);
+            }
+            assert_eq!(board.unmake_move(), Some(mv));
+            assert_eq!(board.to_fen(), fen, "after {}", mv);
+            assert_eq!(board.hash(), hash, "{} after {}", fen, mv);
+        }
+    }
+}
@@ -1,10 +1,17 @@
-use chess_rust::game::Board;
-use chess_rust::types::{Color, Move, Square};
+use chess_rust::bitboard;
+use chess_rust::fen::{FenError, STARTING_FEN};
+use chess_rust::game::{Board, DrawReason, Outcome};
+use chess_rust::history::GameHistory;
+use chess_rust::pgn::{GameResult, PgnErrorKind, PgnReader, Tags, read_pgn, write_pgn};
+use chess_rust::san::SanError;
+use chess_rust::types::{Color, Move, Piece, PieceKind, Square};
+use chess_rust::ui::UI;
 
 fn mv(f1: u8, r1: u8, f2: u8, r2: u8) -> Move {
     Move {
         from: Square(f1, r1),
         to: Square(f2, r2),
+        promotion: None,
     }
 }
 
@@ -67,5 +74,820 @@ fn cannot_capture_own_piece() {
 fn turn_switches_after_legal_move() {
     let mut b = Board::new();
     assert!(b.make_move(mv(4, 1, 4, 2)));
-    assert_eq!(b.turn, Color::Black);
+    assert_eq!(b.turn(), Color::Black);
+}
+
+#[test]
+fn fen_start_position_round_trips() {
+    let b = Board::from_fen(STARTING_FEN).unwrap();
+    assert_eq!(b.to_fen(), STARTING_FEN);
+    assert_eq!(Board::new().to_fen(), STARTING_FEN);
+}
+
+#[test]
+fn fen_round_trips_all_fields() {
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 3 12";
+    let b = Board::from_fen(fen).unwrap();
+    assert_eq!(b.turn(), Color::Black);
+    assert_eq!(b.castling_rights(), [true, false, false, true]);
+    assert_eq!(b.halfmove_clock, 3);
+    assert_eq!(b.fullmove_number, 12);
+    assert_eq!(b.to_fen(), fen);
+}
+
+#[test]
+fn fen_tracks_en_passant_and_counters() {
+    let mut b = Board::new();
+    assert!(b.make_move(mv(4, 1, 4, 3)));
+    assert_eq!(
+        b.to_fen(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+    assert!(b.make_move(mv(6, 7, 5, 5)));
+    assert_eq!(
+        b.to_fen(),
+        "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2"
+    );
+}
+
+#[test]
+fn fen_rejects_malformed_input() {
+    assert_eq!(
+        Board::from_fen("8/8/8 w - - 0 1").err(),
+        Some(FenError::RankCount(3))
+    );
+    assert_eq!(
+        Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq").err(),
+        Some(FenError::FieldCount(3))
+    );
+    assert_eq!(
+        Board::from_fen("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").err(),
+        Some(FenError::RankLength { rank: 7, files: 7 })
+    );
+    assert_eq!(
+        Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1").err(),
+        Some(FenError::InvalidPiece('X'))
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/8 w - - 0 1").err(),
+        Some(FenError::KingCount {
+            color: Color::White,
+            count: 0
+        })
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").err(),
+        Some(FenError::InvalidSideToMove("x".to_string()))
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/4K3 w KK - 0 1").err(),
+        Some(FenError::InvalidCastling("KK".to_string()))
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1").err(),
+        Some(FenError::CastlingWithoutPieces('K'))
+    );
+    assert_eq!(
+        Board::from_fen("r3k3/8/8/8/8/8/8/R3K2R w KQk - 0 1").err(),
+        Some(FenError::CastlingWithoutPieces('k'))
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1").err(),
+        Some(FenError::OpponentInCheck)
+    );
+    assert!(Board::from_fen("4k3/8/8/8/8/8/4R3/4K3 b - - 0 1").is_ok());
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - e3 0 1").err(),
+        Some(FenError::InvalidEnPassant("e3".to_string()))
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").err(),
+        Some(FenError::InvalidHalfmoveClock("x".to_string()))
+    );
+    assert_eq!(
+        Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").err(),
+        Some(FenError::InvalidFullmoveNumber("0".to_string()))
+    );
+}
+
+fn promo(f1: u8, r1: u8, f2: u8, r2: u8, kind: PieceKind) -> Move {
+    Move {
+        promotion: Some(kind),
+        ..mv(f1, r1, f2, r2)
+    }
+}
+
+#[test]
+fn underpromotion_to_knight() {
+    let mut b = Board::from_fen("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+    assert!(b.make_move(promo(4, 6, 4, 7, PieceKind::Knight)));
+    assert_eq!(
+        b.get(Square(4, 7)),
+        Some(Piece {
+            kind: PieceKind::Knight,
+            color: Color::White
+        })
+    );
+}
+
+#[test]
+fn promotion_piece_is_required_on_last_rank() {
+    let mut b = Board::from_fen("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+    assert!(!b.make_move(mv(4, 6, 4, 7)));
+    assert!(!b.make_move(promo(4, 6, 4, 7, PieceKind::King)));
+    assert!(!b.make_move(promo(4, 0, 4, 1, PieceKind::Queen)));
+}
+
+#[test]
+fn generates_four_moves_per_promoting_pawn() {
+    let b = Board::from_fen("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+    let promotions = b
+        .generate_legal_moves(Color::White)
+        .into_iter()
+        .filter(|m| m.promotion.is_some())
+        .count();
+    assert_eq!(promotions, 8);
+}
+
+#[test]
+fn promotion_round_trips_through_ui_notation() {
+    let m = UI::parseMove("e7e8n").unwrap();
+    assert_eq!(m, promo(4, 6, 4, 7, PieceKind::Knight));
+    assert_eq!(UI::move_to_string(m), "e7e8n");
+    assert_eq!(UI::move_to_string(mv(4, 1, 4, 3)), "e2e4");
+    assert!(UI::parseMove("e7e8k").is_none());
+}
+
+#[test]
+fn castling_kingside_when_path_is_safe() {
+    let mut b = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert!(b.make_move(mv(4, 0, 6, 0)));
+    assert_eq!(b.get(Square(5, 0)).map(|p| p.kind), Some(PieceKind::Rook));
+    assert!(b.make_move(mv(4, 7, 2, 7)));
+    assert_eq!(b.get(Square(3, 7)).map(|p| p.kind), Some(PieceKind::Rook));
+}
+
+#[test]
+fn cannot_castle_out_of_check() {
+    let mut b = Board::from_fen("4k3/8/4r3/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    assert!(!b.make_move(mv(4, 0, 6, 0)));
+    assert!(!b.make_move(mv(4, 0, 2, 0)));
+}
+
+#[test]
+fn cannot_castle_kingside_through_check() {
+    let mut b = Board::from_fen("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    assert!(!b.make_move(mv(4, 0, 6, 0)));
+    assert!(b.make_move(mv(4, 0, 2, 0)));
+}
+
+#[test]
+fn cannot_castle_queenside_through_check() {
+    let mut b = Board::from_fen("3rk3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    assert!(!b.make_move(mv(4, 0, 2, 0)));
+    assert!(b.make_move(mv(4, 0, 6, 0)));
+}
+
+#[test]
+fn cannot_castle_into_check() {
+    let mut b = Board::from_fen("2r1k1r1/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    assert!(!b.make_move(mv(4, 0, 6, 0)));
+    assert!(!b.make_move(mv(4, 0, 2, 0)));
+}
+
+#[test]
+fn castling_queenside_allowed_when_only_b_file_is_attacked() {
+    let mut b = Board::from_fen("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    assert!(b.make_move(mv(4, 0, 2, 0)));
+}
+
+#[test]
+fn black_cannot_castle_through_check() {
+    let mut b = Board::from_fen("r3k2r/8/8/8/8/8/8/3RK1R1 b kq - 0 1").unwrap();
+    assert!(!b.make_move(mv(4, 7, 2, 7)));
+    assert!(!b.make_move(mv(4, 7, 6, 7)));
+}
+
+#[test]
+fn castling_moves_are_not_generated_through_check() {
+    let b = Board::from_fen("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    let moves = b.generate_legal_moves(Color::White);
+    assert!(!moves.contains(&mv(4, 0, 6, 0)));
+    assert!(moves.contains(&mv(4, 0, 2, 0)));
+}
+
+fn play(b: &mut Board, moves: &[&str]) {
+    for m in moves {
+        assert!(b.make_move(UI::parseMove(m).unwrap()), "illegal: {}", m);
+    }
+}
+
+#[test]
+fn threefold_repetition_is_detected() {
+    let mut b = Board::new();
+    play(
+        &mut b,
+        &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"],
+    );
+    assert!(!b.is_threefold_repetition());
+    play(&mut b, &["f6g8"]);
+    assert!(b.is_threefold_repetition());
+    // A claimable draw does not end the game on its own.
+    assert_eq!(b.outcome(), None);
+    assert_eq!(b.claimable_draw(), Some(DrawReason::ThreefoldRepetition));
+}
+
+#[test]
+fn fivefold_repetition_takes_precedence() {
+    let mut b = Board::new();
+    for _ in 0..4 {
+        play(&mut b, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    }
+    assert_eq!(
+        b.outcome(),
+        Some(Outcome::Draw(DrawReason::FivefoldRepetition))
+    );
+}
+
+#[test]
+fn double_push_without_en_passant_reply_still_repeats() {
+    let mut b = Board::new();
+    play(&mut b, &["e2e4", "g8f6", "g1f3", "f6g8", "f3g1", "g8f6"]);
+    play(&mut b, &["g1f3", "f6g8", "f3g1"]);
+    assert!(b.is_threefold_repetition());
+}
+
+#[test]
+fn fifty_and_seventy_five_move_rules() {
+    let b = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+    assert!(!b.is_fifty_move_draw());
+    assert_eq!(b.claimable_draw(), None);
+
+    let mut b = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+    play(&mut b, &["a1a2"]);
+    assert!(b.is_fifty_move_draw());
+    assert_eq!(b.outcome(), None);
+    assert_eq!(b.claimable_draw(), Some(DrawReason::FiftyMoveRule));
+
+    let b = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 100").unwrap();
+    assert_eq!(
+        b.outcome(),
+        Some(Outcome::Draw(DrawReason::SeventyFiveMoveRule))
+    );
+}
+
+#[test]
+fn pawn_move_resets_halfmove_clock() {
+    let mut b = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap();
+    play(&mut b, &["e2e4"]);
+    assert_eq!(b.halfmove_clock, 0);
+    assert!(!b.is_fifty_move_draw());
+}
+
+#[test]
+fn insufficient_material() {
+    let dead = [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+        "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+    ];
+    for fen in dead {
+        let b = Board::from_fen(fen).unwrap();
+        assert!(b.is_insufficient_material(), "{}", fen);
+        assert_eq!(
+            b.outcome(),
+            Some(Outcome::Draw(DrawReason::InsufficientMaterial))
+        );
+    }
+
+    let alive = [
+        "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+        "4kb2/8/8/8/8/8/8/3BK3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/3NKN2 w - - 0 1",
+    ];
+    for fen in alive {
+        let b = Board::from_fen(fen).unwrap();
+        assert!(!b.is_insufficient_material(), "{}", fen);
+    }
+}
+
+#[test]
+fn outcome_reports_checkmate_and_stalemate() {
+    let mut b = Board::new();
+    play(&mut b, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+    assert_eq!(
+        b.outcome(),
+        Some(Outcome::Checkmate {
+            winner: Color::Black
+        })
+    );
+
+    let b = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(b.outcome(), Some(Outcome::Draw(DrawReason::Stalemate)));
+}
+
+#[test]
+fn unmake_move_restores_every_kind_of_move() {
+    let cases = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "e2e4",
+        ),
+        ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 5 20", "e4d5"),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 30", "e5d6"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 2 10", "e1g1"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 2 10", "e8c8"),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 2 10", "a1a8"),
+        ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 40", "a7b8n"),
+    ];
+    for (fen, m) in cases {
+        let mut b = Board::from_fen(fen).unwrap();
+        let m = UI::parseMove(m).unwrap();
+        assert!(b.make_move(m), "{} {:?}", fen, m);
+        assert_ne!(b.to_fen(), fen);
+        assert_eq!(b.unmake_move(), Some(m));
+        assert_eq!(b.to_fen(), fen);
+    }
+}
+
+#[test]
+fn unmake_move_on_fresh_board_does_nothing() {
+    let mut b = Board::new();
+    assert_eq!(b.unmake_move(), None);
+    assert_eq!(b.to_fen(), STARTING_FEN);
+}
+
+#[test]
+fn unmake_move_rewinds_repetition_history() {
+    let mut b = Board::new();
+    play(
+        &mut b,
+        &[
+            "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8",
+        ],
+    );
+    assert!(b.is_threefold_repetition());
+    b.unmake_move();
+    b.unmake_move();
+    assert!(!b.is_threefold_repetition());
+}
+
+#[test]
+fn game_history_undo_and_redo() {
+    let mut g = GameHistory::new(Board::new());
+    assert!(!g.can_undo());
+    assert!(g.play(UI::parseMove("e2e4").unwrap()));
+    assert!(g.play(UI::parseMove("e7e5").unwrap()));
+
+    assert_eq!(g.undo(), UI::parseMove("e7e5"));
+    assert_eq!(g.undo(), UI::parseMove("e2e4"));
+    assert_eq!(g.undo(), None);
+    assert_eq!(g.board().to_fen(), STARTING_FEN);
+
+    assert_eq!(g.redo(), UI::parseMove("e2e4"));
+    assert_eq!(g.board().turn(), Color::Black);
+    assert!(g.can_redo());
+
+    assert!(g.play(UI::parseMove("c7c5").unwrap()));
+    assert!(!g.can_redo());
+    assert_eq!(g.redo(), None);
+}
+
+#[test]
+fn move_history_string_is_numbered_san() {
+    let mut b = Board::new();
+    assert_eq!(b.moveHistoryString(), "");
+    play(&mut b, &["e2e4", "e7e5", "g1f3"]);
+    assert_eq!(b.moveHistoryString(), "1. e4 e5 2. Nf3");
+    assert_eq!(b.uci_moves(), "e2e4 e7e5 g1f3");
+}
+
+#[test]
+fn move_history_string_from_black_to_move() {
+    let mut b =
+        Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    play(&mut b, &["c7c5", "g1f3"]);
+    assert_eq!(b.moveHistoryString(), "1... c5 2. Nf3");
+}
+
+#[test]
+fn numbered_san_tokens_stop_at_an_illegal_move() {
+    let b = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    let moves: Vec<Move> = ["c7c5", "g1f3", "e1e3", "b8c6"]
+        .iter()
+        .map(|m| UI::parseMove(m).unwrap())
+        .collect();
+    assert_eq!(b.numbered_san(&moves), ["1...", "c5", "2.", "Nf3"]);
+    assert!(b.numbered_san(&[]).is_empty());
+}
+
+#[test]
+fn san_generation() {
+    let cases = [
+        (STARTING_FEN, "g1f3", "Nf3"),
+        ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5", "exd5"),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", "exd6"),
+        ("r3k3/8/8/8/8/8/8/4K2R w K - 0 1", "e1g1", "O-O"),
+        ("r3k3/8/8/8/8/8/8/3K3R b q - 0 1", "e8c8", "O-O-O+"),
+        ("3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7d8q", "exd8=Q+"),
+        ("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "b1d2", "Nbd2"),
+        ("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1d1", "Rad1"),
+        ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3"),
+        ("4k3/8/8/8/8/8/8/Q1Q1K3 b - - 0 1", "e8e7", "Ke7"),
+        ("7k/8/8/2N5/8/2N3N1/8/7K w - - 0 1", "c3e4", "Nc3e4"),
+        ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", "Ra8#"),
+    ];
+    for (fen, m, san) in cases {
+        let b = Board::from_fen(fen).unwrap();
+        assert_eq!(b.to_san(UI::parseMove(m).unwrap()), san, "{}", fen);
+    }
+}
+
+#[test]
+fn san_parsing() {
+    let cases = [
+        (STARTING_FEN, "Nf3", "g1f3"),
+        (STARTING_FEN, "e4", "e2e4"),
+        ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "exd5", "e4d5"),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6", "e5d6"),
+        ("r3k3/8/8/8/8/8/8/4K2R w K - 0 1", "O-O", "e1g1"),
+        ("r3k3/8/8/8/8/8/8/3K3R b q - 0 1", "O-O-O+", "e8c8"),
+        ("3rk3/4P3/8/8/8/8/8/4K3 w - - 0 1", "exd8=N", "e7d8n"),
+        ("3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1", "e8Q+", "e7e8q"),
+        ("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "Nbd2", "b1d2"),
+        ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "R1a3", "a1a3"),
+        ("7k/8/8/2N5/8/2N3N1/8/7K w - - 0 1", "Nc3e4", "c3e4"),
+        ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "Ra8#", "a1a8"),
+    ];
+    for (fen, san, expected) in cases {
+        let b = Board::from_fen(fen).unwrap();
+        assert_eq!(
+            b.parse_san(san),
+            Ok(UI::parseMove(expected).unwrap()),
+            "{}",
+            san
+        );
+    }
+}
+
+#[test]
+fn san_round_trips_every_legal_move() {
+    let fens = [
+        STARTING_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "1r2k3/P1P5/8/8/8/8/8/4K3 w - - 0 1",
+    ];
+    for fen in fens {
+        let b = Board::from_fen(fen).unwrap();
+        for m in b.generate_legal_moves(b.turn()) {
+            let san = b.to_san(m);
+            assert_eq!(b.parse_san(&san), Ok(m), "{} {}", fen, san);
+        }
+    }
+}
+
+#[test]
+fn san_errors() {
+    let b = Board::new();
+    assert_eq!(
+        b.parse_san("Nf4"),
+        Err(SanError::Illegal("Nf4".to_string()))
+    );
+    assert_eq!(b.parse_san("Zf3"), Err(SanError::Syntax("Zf3".to_string())));
+    assert_eq!(b.parse_san("e9"), Err(SanError::Syntax("e9".to_string())));
+    assert_eq!(b.parse_san(""), Err(SanError::Syntax("".to_string())));
+
+    let b = Board::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+    assert_eq!(
+        b.parse_san("Nd2"),
+        Err(SanError::Ambiguous("Nd2".to_string()))
+    );
+
+    let b = Board::from_fen("4k3/4P3/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    assert_eq!(b.parse_san("e8"), Err(SanError::Illegal("e8".to_string())));
+}
+
+#[test]
+fn ui_accepts_coordinates_and_san() {
+    let b = Board::new();
+    assert_eq!(UI::parse_input(&b, "G1F3"), Ok(mv(6, 0, 5, 2)));
+    assert_eq!(UI::parse_input(&b, "Nf3"), Ok(mv(6, 0, 5, 2)));
+    assert!(UI::parse_input(&b, "Nf4").is_err());
+}
+
+#[test]
+fn pgn_export_standard_game() {
+    let mut b = Board::new();
+    play(&mut b, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+    let tags = Tags {
+        event: "Club \"Blitz\"".to_string(),
+        white: "Alice".to_string(),
+        black: "Bob".to_string(),
+        ..Tags::default()
+    };
+    let result = GameResult::from_outcome(b.outcome());
+    assert_eq!(result, GameResult::BlackWins);
+    assert_eq!(
+        write_pgn(&b, &tags, result),
+        "[Event \"Club \\\"Blitz\\\"\"]\n\
+         [Site \"?\"]\n\
+         [Date \"????.??.??\"]\n\
+         [Round \"?\"]\n\
+         [White \"Alice\"]\n\
+         [Black \"Bob\"]\n\
+         [Result \"0-1\"]\n\
+         \n\
+         1. f3 e5 2. g4 Qh4# 0-1\n\n"
+    );
+}
+
+#[test]
+fn pgn_export_custom_start_has_fen_tag() {
+    let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 30";
+    let mut b = Board::from_fen(fen).unwrap();
+    play(&mut b, &["e8d7", "e2e4"]);
+    let pgn = write_pgn(&b, &Tags::default(), GameResult::Unfinished);
+    assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]\n"));
+    assert!(pgn.ends_with("\n30... Kd7 31. e4 *\n\n"));
+}
+
+#[test]
+fn pgn_export_wraps_long_movetext() {
+    let mut b = Board::new();
+    for _ in 0..6 {
+        play(&mut b, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    }
+    let pgn = write_pgn(&b, &Tags::default(), GameResult::Draw);
+    let movetext: Vec<&str> = pgn.split("\n\n").nth(1).unwrap().lines().collect();
+    assert!(movetext.len() > 1);
+    assert!(movetext.iter().all(|line| line.len() < 80));
+    assert!(movetext.last().unwrap().ends_with("1/2-1/2"));
+}
+
+const TWO_GAMES: &str = r#"[Event "Casual"]
+[Site "?"]
+[White "Alice"]
+[Black "Bob \"the Rook\""]
+[Result "1-0"]
+
+1. e4 {King's pawn} e5 2. Nf3 $1 Nc6 (2... d6 3. d4 (3. Bc4) exd4) 3. Bb5 a6
+; a rest-of-line comment
+4.Ba4 Nf6 5. O-O!? 1-0
+
+[Event "Endgame"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 30"]
+[Result "*"]
+
+30... Kd7 31. e4 *
+"#;
+
+#[test]
+fn pgn_import_multiple_games() {
+    let games = read_pgn(TWO_GAMES).unwrap();
+    assert_eq!(games.len(), 2);
+
+    let first = &games[0];
+    assert_eq!(first.tag("Black"), Some("Bob \"the Rook\""));
+    assert_eq!(first.result, GameResult::WhiteWins);
+    assert_eq!(
+        first.board.moveHistoryString(),
+        "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O"
+    );
+
+    let second = &games[1];
+    assert_eq!(second.result, GameResult::Unfinished);
+    assert_eq!(second.board.moveHistoryString(), "30... Kd7 31. e4");
+    assert_eq!(second.board.fullmove_number, 31);
+}
+
+#[test]
+fn pgn_import_accepts_castling_written_with_zeros() {
+    let games = read_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 *").unwrap();
+    assert_eq!(
+        games[0].board.moveHistoryString(),
+        "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O"
+    );
+
+    let text = "1. d4 d5 2. Nc3 Nc6 3. Bf4 Bf5 4. Qd2 Qd7 5.0-0-0 0-0-0 *";
+    let games = read_pgn(text).unwrap();
+    assert_eq!(
+        games[0].board.moveHistoryString(),
+        "1. d4 d5 2. Nc3 Nc6 3. Bf4 Bf5 4. Qd2 Qd7 5. O-O-O O-O-O"
+    );
+}
+
+#[test]
+fn pgn_round_trips_through_writer() {
+    let mut b = Board::new();
+    play(&mut b, &["d2d4", "g8f6", "c2c4", "e7e6", "b1c3", "f8b4"]);
+    let text = write_pgn(&b, &Tags::default(), GameResult::Unfinished);
+    let games = read_pgn(&text).unwrap();
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].board.to_fen(), b.to_fen());
+    assert_eq!(games[0].tag("Result"), Some("*"));
+}
+
+#[test]
+fn pgn_import_reports_game_and_ply() {
+    let text = "1. e4 e5 *\n\n[Event \"Bad\"]\n\n1. e4 e5 2. Ke3 Nc6 *\n";
+    let err = read_pgn(text).err().unwrap();
+    assert_eq!(err.game, 2);
+    assert_eq!(err.ply, 3);
+    assert_eq!(
+        err.kind,
+        PgnErrorKind::Move {
+            san: "Ke3".to_string(),
+            error: SanError::Illegal("Ke3".to_string()),
+        }
+    );
+    assert_eq!(err.to_string(), "game 2, ply 3: 'Ke3' is not a legal move");
+}
+
+#[test]
+fn pgn_import_reports_ambiguous_moves() {
+    let text = "[FEN \"4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1\"]\n\n1. Nd2 *";
+    let err = read_pgn(text).err().unwrap();
+    assert_eq!((err.game, err.ply), (1, 1));
+    assert!(matches!(
+        err.kind,
+        PgnErrorKind::Move {
+            error: SanError::Ambiguous(_),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn pgn_reader_continues_after_a_bad_game() {
+    let text = "1. e4 e5 2. Qxf7 *\n\n[Event \"Good\"]\n\n1. d4 d5 1/2-1/2\n";
+    let results: Vec<_> = PgnReader::new(text).collect();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_err());
+    let good = results[1].as_ref().ok().unwrap();
+    assert_eq!(good.tag("Event"), Some("Good"));
+    assert_eq!(good.result, GameResult::Draw);
+}
+
+#[test]
+fn pgn_tags_without_movetext_are_a_game_of_their_own() {
+    let games = read_pgn("[Event \"A\"]\n\n[Event \"B\"]\n\n1. e4 e5 1-0").unwrap();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].tags, [("Event".to_string(), "A".to_string())]);
+    assert_eq!(games[0].result, GameResult::Unfinished);
+    assert_eq!(games[1].tag("Event"), Some("B"));
+    assert_eq!(games[1].result, GameResult::WhiteWins);
+
+    let text = "[Event \"A\"]\n{ no moves }\n[Event \"B\"]\n\n1. e4 e5 2. Ke3 *";
+    let err = read_pgn(text).err().unwrap();
+    assert_eq!((err.game, err.ply), (2, 3));
+}
+
+#[test]
+fn pgn_import_structural_errors() {
+    let err = read_pgn("1. e4 { never closed").err().unwrap();
+    assert_eq!(err.kind, PgnErrorKind::UnterminatedComment);
+
+    let err = read_pgn("1. e4 (1. d4 *").err().unwrap();
+    assert_eq!(err.kind, PgnErrorKind::UnbalancedVariation);
+
+    let err = read_pgn("[Event Casual]\n1. e4 *").err().unwrap();
+    assert_eq!(
+        err.kind,
+        PgnErrorKind::MalformedTag("[Event Casual]".to_string())
+    );
+
+    let err = read_pgn("[FEN \"8/8/8 w - - 0 1\"]\n*").err().unwrap();
+    assert_eq!(err.kind, PgnErrorKind::Fen(FenError::RankCount(3)));
+}
+
+#[test]
+fn get_and_set_keep_working_on_bitboards() {
+    let mut b = Board::empty();
+    let knight = Piece {
+        kind: PieceKind::Knight,
+        color: Color::Black,
+    };
+    b.set(Square(3, 4), Some(knight));
+    assert_eq!(b.get(Square(3, 4)), Some(knight));
+    assert_eq!(b.pieces(Color::Black, PieceKind::Knight), 1 << 35);
+    b.set(Square(3, 4), None);
+    assert_eq!(b.get(Square(3, 4)), None);
+    assert_eq!(b.occupied(), 0);
+    assert_eq!(b.get(Square(8, 0)), None);
+}
+
+#[test]
+fn set_keeps_the_en_passant_hash_in_step() {
+    let white_pawn = Piece {
+        kind: PieceKind::Pawn,
+        color: Color::White,
+    };
+    // A pawn placed next to the double-pushed one makes the capture possible.
+    let mut b = Board::from_fen("4k3/8/8/3p4/8/8/8/4K3 w - d6 0 1").unwrap();
+    b.set(Square(4, 4), Some(white_pawn));
+    let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+    assert_eq!(b.hash(), Board::from_fen(fen).unwrap().hash());
+    play(&mut b, &["e5d6"]);
+    assert_eq!(b.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+
+    // Taking it away again makes the en passant square irrelevant.
+    let mut b = Board::from_fen(fen).unwrap();
+    b.set(Square(4, 4), None);
+    assert_eq!(
+        b.hash(),
+        Board::from_fen("4k3/8/8/3p4/8/8/8/4K3 w - - 0 1")
+            .unwrap()
+            .hash()
+    );
+    play(&mut b, &["e1e2"]);
+}
+
+#[test]
+fn setters_keep_the_hash_in_step() {
+    let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+    let mut b = Board::from_fen(fen).unwrap();
+    b.set_turn(Color::Black);
+    b.set_en_passant_target(None);
+    b.set_castling_rights([false, true, true, false]);
+    let after = "r3k2r/8/8/3pP3/8/8/8/R3K2R b Qk - 0 1";
+    assert_eq!(b.to_fen(), after);
+    assert_eq!(b.hash(), Board::from_fen(after).unwrap().hash());
+    // Moving afterwards must not trip the incremental hash check.
+    play(&mut b, &["e8g8"]);
+
+    let mut b = Board::new();
+    b.set_turn(Color::Black);
+    play(&mut b, &["e7e5"]);
+    assert_eq!(b.en_passant_target(), Some(Square(4, 5)));
+    b.set_en_passant_target(None);
+    play(&mut b, &["d2d4"]);
+}
+
+#[test]
+fn sliding_attacks_stop_at_blockers() {
+    let b = Board::from_fen("4k3/8/8/4q3/8/4P3/8/4K3 w - - 0 1").unwrap();
+    assert!(b.is_square_attacked(Square(4, 2), Color::Black));
+    assert!(!b.is_square_attacked(Square(4, 1), Color::Black));
+    assert!(!b.is_in_check(Color::White));
+
+    assert_eq!(bitboard::rook_attacks(Square(0, 0), 0).count_ones(), 14);
+    assert_eq!(bitboard::bishop_attacks(Square(3, 3), 0).count_ones(), 13);
+    assert_eq!(bitboard::knight_attacks(Square(0, 0)).count_ones(), 2);
+}
+
+#[test]
+fn transpositions_share_a_hash() {
+    let mut a = Board::new();
+    play(&mut a, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+    let mut b = Board::new();
+    play(&mut b, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+    assert_eq!(a.hash(), b.hash());
+    assert_ne!(a.hash(), Board::new().hash());
+}
+
+#[test]
+fn hash_matches_the_same_position_loaded_from_fen() {
+    let mut b = Board::new();
+    play(&mut b, &["e2e4", "c7c5", "e4e5", "d7d5"]);
+    let fen = "rnbqkbnr/pp2pppp/8/2ppP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3";
+    assert_eq!(b.hash(), Board::from_fen(fen).unwrap().hash());
+}
+
+#[test]
+fn hash_covers_side_castling_and_en_passant() {
+    let base = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+    let hash = |fen: &str| Board::from_fen(fen).unwrap().hash();
+    let no_ep = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq - 0 1";
+    assert_ne!(hash(base), hash(no_ep));
+    assert_ne!(hash(no_ep), hash("r3k2r/8/8/3pP3/8/8/8/R3K2R b KQkq - 0 1"));
+    assert_ne!(hash(base), hash("r3k2r/8/8/3pP3/8/8/8/R3K2R w Qkq d6 0 1"));
+    // An en passant square nobody can capture on does not change the position.
+    assert_eq!(
+        hash("4k3/8/8/3p4/8/8/8/4K3 w - d6 0 1"),
+        hash("4k3/8/8/3p4/8/8/8/4K3 w - - 0 1")
+    );
+}
+
+#[test]
+fn unmake_restores_the_hash() {
+    let mut b = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+    let start = b.hash();
+    play(&mut b, &["e5d6", "e8g8", "b7a8q", "f8a8", "e1c1"]);
+    while b.unmake_move().is_some() {}
+    assert_eq!(b.hash(), start);
+}
+
+#[test]
+fn engine_lines_are_shown_in_numbered_san() {
+    let mut b = Board::new();
+    play(&mut b, &["e2e4"]);
+    let line: Vec<String> = ["e7e5", "g1f3", "b8c6", "f1b5", "e1e1"]
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    assert_eq!(UI::line_to_san(&b, &line), "1... e5 2. Nf3 Nc6 3. Bb5");
+    assert_eq!(UI::line_to_san(&b, &line[1..]), "");
 }